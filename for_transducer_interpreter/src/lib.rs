@@ -1,7 +1,4 @@
-pub mod ast;
-pub mod interpreter;
-pub mod lexer;
-pub mod parser;
+pub use transducer_front_end::{ast, diagnostic, interpreter, lexer, parser};
 
 pub use interpreter::Interpreter;
 pub use lexer::tokenize;
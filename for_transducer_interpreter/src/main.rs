@@ -1,6 +1,8 @@
 use std::env;
 use std::fs;
-use for_transducer_interpreter::{tokenize, Parser, Interpreter};
+use for_transducer_interpreter::diagnostic::render_all;
+use for_transducer_interpreter::lexer::{tokenize_with, Syntax};
+use for_transducer_interpreter::{Interpreter, Parser};
 
 fn main() {
     // Collect command-line arguments
@@ -16,14 +18,40 @@ fn main() {
     // Get the string
     let input_string = &args[2];
 
-    // Tokenize the script
-    let tokens = tokenize(&script);
+    // Tokenize the script and parse the tokens into the transducers, in
+    // the order they run (`.for` files are in the syntax of semantics.md)
+    let syntax = Syntax::of_file(&args[1]);
+    let parsed = tokenize_with(&script, syntax).and_then(|tokens| {
+        let mut parser = Parser::new(tokens);
+        match syntax {
+            Syntax::Braces => parser.parse_pipeline(),
+            Syntax::Paper => parser.parse_paper().map(|program| vec![program]),
+        }
+    });
+    let stages = match parsed {
+        Ok(stages) => stages,
+        Err(diagnostics) => {
+            eprint!("{}", render_all(&diagnostics, &script, &args[1]));
+            std::process::exit(1);
+        }
+    };
 
-    // Parse the tokens into an AST
-    let mut parser = Parser::new(tokens);
-    let stmts = parser.parse();
+    // The letters are separated by whitespace or commas, or are the
+    // symbols of the string (characters, unless the alphabet header
+    // declares another mode)
+    let letters = match stages[0].alphabet.split_word(input_string) {
+        Ok(letters) => letters,
+        Err(letter) => {
+            eprintln!("error: `{}` is not in the input alphabet", letter);
+            std::process::exit(1);
+        }
+    };
 
-    // Interpret the AST
-    let mut interpreter = Interpreter::new(input_string);
-    interpreter.interpret(stmts);
+    // Interpret the transducers, each one reading the output of the
+    // previous one
+    let mut output = letters;
+    for stage in &stages {
+        output = Interpreter::with_letters(output).run_letters(&stage.stmts);
+    }
+    println!("{}", stages[stages.len() - 1].alphabet.join_word(&output));
 }
@@ -0,0 +1,18 @@
+use for_transducer_interpreter::{tokenize, Parser, Interpreter};
+use std::fs;
+
+#[test]
+fn test_example1() {
+    let script = fs::read_to_string("examples/identity.txt").expect("Unable to read identity.txt");
+
+    // Tokenize the script
+    let tokens = tokenize(&script).expect("Unable to tokenize identity.txt");
+
+    // Parse the tokens into an AST
+    let mut parser = Parser::new(tokens);
+    let program = parser.parse().expect("Unable to parse identity.txt");
+
+    // Interpret the AST
+    let mut interpreter = Interpreter::new("ab#");
+    assert_eq!(interpreter.run(&program.stmts), "ab#");
+}
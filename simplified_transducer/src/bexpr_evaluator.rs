@@ -1,14 +1,16 @@
+use crate::lexer::LENGTH_VARIABLE;
 use crate::Bexpr;
 
 #[derive(Debug, Clone)]
 pub enum Value {
-    Number(usize),
+    Number(i64),
     Str(String),
 }
 
 pub struct Evaluator {
     pub variables: std::collections::HashMap<String, usize>,
-    pub word: String,
+    // the letters of the word
+    pub word: Vec<String>,
 }
 
 impl Evaluator {
@@ -21,74 +23,20 @@ impl Evaluator {
 
         //println!("{:?}", expr);
         match expr {
-            Bexpr::Var(name) => {
-                if name == "T" {
-                    Value::Number(1)
-                } else if name == "F" {
-                    Value::Number(0)
-                } else {
-                    match self.variables.get(name) {
-                        Some(value) => Value::Number(*value),
-                        None => panic!("Variable {} not defined", name),
-                    }
-                }
-            }
+            Bexpr::Var(name) => Value::Number(self.position(name)),
+            Bexpr::True => Value::Number(1),
+            Bexpr::False => Value::Number(0),
             Bexpr::Str(s) => {
-                // Check for a(var_name), b(var_name), or #(var_name)
-
-                if s == "T" {
+                if s == "true" {
                     Value::Number(1)
-                } else if s == "F" {
+                } else if s == "false" {
                     Value::Number(0)
-                }
-
-                else if s.starts_with('a') && s.ends_with(')') && s.len() > 2 && s.chars().nth(1) == Some('(') {
-                    let var_name = &s[2..s.len()-1];
-                    match self.variables.get(var_name) {
-                        Some(value) => {
-                            if let Some(character) = self.word.chars().nth(*value) {
-                                if character == 'a' {
-                                    Value::Number(1)
-                                } else {
-                                    Value::Number(0)
-                                }
-                            } else {
-                                panic!("Index out of bounds");
-                            }
-                        }
-                        None => panic!("Variable {} not defined", var_name),
-                    }
-                } else if s.starts_with('b') && s.ends_with(')') && s.len() > 2 && s.chars().nth(1) == Some('(') {
-                    let var_name = &s[2..s.len()-1];
-                    match self.variables.get(var_name) {
-                        Some(value) => {
-                            if let Some(character) = self.word.chars().nth(*value) {
-                                if character == 'b' {
-                                    Value::Number(1)
-                                } else {
-                                    Value::Number(0)
-                                }
-                            } else {
-                                panic!("Index out of bounds");
-                            }
-                        }
-                        None => panic!("Variable {} not defined", var_name),
-                    }
-                } else if s.starts_with('#') && s.ends_with(')') && s.len() > 2 && s.chars().nth(1) == Some('(') {
-                    let var_name = &s[2..s.len()-1];
-                    match self.variables.get(var_name) {
-                        Some(value) => {
-                            if let Some(character) = self.word.chars().nth(*value) {
-                                if character == '#' {
-                                    Value::Number(1)
-                                } else {
-                                    Value::Number(0)
-                                }
-                            } else {
-                                panic!("Index out of bounds");
-                            }
-                        }
-                        None => panic!("Variable {} not defined", var_name),
+                } else if let Some((letter, var_name)) = self.letter_at_position(s) {
+                    // letter formula of the form letter(var_name)
+                    let position = self.position(var_name) as usize;
+                    match self.word.get(position) {
+                        Some(character) => Value::Number((character == letter) as i64),
+                        None => panic!("Index out of bounds"),
                     }
                 } else {
                     Value::Str(s.clone())
@@ -97,8 +45,8 @@ impl Evaluator {
 
             Bexpr::Label(name) => match self.variables.get(name) {
                 Some(value) => {
-                    if let Some(character) = self.word.chars().nth(*value) {
-                        Value::Str(character.to_string())
+                    if let Some(letter) = self.word.get(*value) {
+                        Value::Str(letter.clone())
                     } else {
                         panic!("Index out of bounds");
                     }
@@ -111,22 +59,22 @@ impl Evaluator {
             | Bexpr::NotEqual(left, right)
             | Bexpr::Greater(left, right)
             | Bexpr::GreaterEqual(left, right) => {
-                let left_val = self.evaluate_bexpr(left);
-                let right_val = self.evaluate_bexpr(right);
+                let left_val = self.evaluate_operand(left);
+                let right_val = self.evaluate_operand(right);
     
                 match (left_val, right_val) {
                     (Value::Number(lv), Value::Number(rv)) => match expr {
-                        Bexpr::LessEqual(_, _) => Value::Number((lv <= rv) as usize),
-                        Bexpr::Less(_, _) => Value::Number((lv < rv) as usize),
-                        Bexpr::Equal(_, _) => Value::Number((lv == rv) as usize),
-                        Bexpr::NotEqual(_, _) => Value::Number((lv != rv) as usize),
-                        Bexpr::Greater(_, _) => Value::Number((lv > rv) as usize),
-                        Bexpr::GreaterEqual(_, _) => Value::Number((lv >= rv) as usize),
+                        Bexpr::LessEqual(_, _) => Value::Number((lv <= rv) as i64),
+                        Bexpr::Less(_, _) => Value::Number((lv < rv) as i64),
+                        Bexpr::Equal(_, _) => Value::Number((lv == rv) as i64),
+                        Bexpr::NotEqual(_, _) => Value::Number((lv != rv) as i64),
+                        Bexpr::Greater(_, _) => Value::Number((lv > rv) as i64),
+                        Bexpr::GreaterEqual(_, _) => Value::Number((lv >= rv) as i64),
                         _ => panic!("Unexpected comparison"),
                     },
                     (Value::Str(ls), Value::Str(rs)) => match expr {
-                        Bexpr::Equal(_, _) => Value::Number((ls == rs) as usize),
-                        Bexpr::NotEqual(_, _) => Value::Number((ls != rs) as usize),
+                        Bexpr::Equal(_, _) => Value::Number((ls == rs) as i64),
+                        Bexpr::NotEqual(_, _) => Value::Number((ls != rs) as i64),
                         _ => panic!(
                             "Invalid comparison: only equality comparison with labels is allowed"
                         ),
@@ -137,32 +85,76 @@ impl Evaluator {
             Bexpr::Not(inner) => {
                 let inner_val = self.evaluate_bexpr(inner);
                 match inner_val {
-                    Value::Number(n) => Value::Number((n == 0) as usize),
+                    Value::Number(n) => Value::Number((n == 0) as i64),
                     _ => panic!("Invalid type for Not operation"),
                 }
             }
+            // the right side is only evaluated when the left one does not
+            // decide, which skips the quantifiers guarded by a false
+            // universe formula in a composition
             Bexpr::And(left, right) => {
-                let left_val = self.evaluate_bexpr(left);
-                let right_val = self.evaluate_bexpr(right);
-    
-                match (left_val, right_val) {
-                    (Value::Number(lv), Value::Number(rv)) => {
-                        Value::Number(((lv != 0) && (rv != 0)) as usize)
-                    }
-                    _ => panic!("Invalid types for And operation"),
-                }
+                let result = self.evaluate_condition(left) && self.evaluate_condition(right);
+                Value::Number(result as i64)
             }
             Bexpr::Or(left, right) => {
-                let left_val = self.evaluate_bexpr(left);
-                let right_val = self.evaluate_bexpr(right);
-    
-                match (left_val, right_val) {
-                    (Value::Number(lv), Value::Number(rv)) => {
-                        Value::Number(((lv != 0) || (rv != 0)) as usize)
+                let result = self.evaluate_condition(left) || self.evaluate_condition(right);
+                Value::Number(result as i64)
+            }
+            Bexpr::Exists(var, inner) | Bexpr::Forall(var, inner) => {
+                // try every position of the word for the bound variable
+                let exists = matches!(expr, Bexpr::Exists(_, _));
+                let previous = self.variables.remove(var);
+                let mut result = !exists;
+                for position in 0..self.word.len() {
+                    self.variables.insert(var.clone(), position);
+                    if self.evaluate_condition(inner) == exists {
+                        result = exists;
+                        break;
                     }
-                    _ => panic!("Invalid types for Or operation"),
                 }
+                self.variables.remove(var);
+                if let Some(value) = previous {
+                    self.variables.insert(var.clone(), value);
+                }
+                Value::Number(result as i64)
             }
+            Bexpr::Offset(name, offset) => Value::Number(self.position(name) + *offset as i64),
+            Bexpr::Number(n) => Value::Number(*n as i64),
+            Bexpr::LetterIn(name, letters) => match self.evaluate_bexpr(&Bexpr::Label(name.clone())) {
+                Value::Str(letter) => Value::Number(letters.contains(&letter) as i64),
+                Value::Number(_) => unreachable!(),
+            },
+            Bexpr::Flag(name) => panic!("Boolean variable {} was not compiled away", name),
+        }
+    }
+
+    // Strings compared with labels are letters, even `T` or `F`
+    fn evaluate_operand(&mut self, expr: &Bexpr) -> Value {
+        match expr {
+            Bexpr::Str(s) => Value::Str(s.clone()),
+            _ => self.evaluate_bexpr(expr),
+        }
+    }
+
+    // Split a formula `letter(var_name)` on a bound variable
+    fn letter_at_position<'s>(&self, s: &'s str) -> Option<(&'s str, &'s str)> {
+        let inside = s.strip_suffix(')')?;
+        let open = inside.rfind('(')?;
+        let var_name = &inside[open + 1..];
+        if open == 0 || !self.variables.contains_key(var_name) {
+            return None;
+        }
+        Some((&inside[..open], var_name))
+    }
+
+    // Value of a position variable, `n` being the length of the word
+    fn position(&self, name: &str) -> i64 {
+        if name == LENGTH_VARIABLE {
+            return self.word.len() as i64;
+        }
+        match self.variables.get(name) {
+            Some(value) => *value as i64,
+            None => panic!("Variable {} not defined", name),
         }
     }
 
@@ -0,0 +1,11 @@
+use simplified_transducer::lsp::serve;
+
+use std::io;
+
+fn main() {
+    // The client talks to the server on stdin and stdout
+    if let Err(error) = serve(io::stdin().lock(), io::stdout().lock()) {
+        eprintln!("error: {}", error);
+        std::process::exit(1);
+    }
+}
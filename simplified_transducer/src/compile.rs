@@ -0,0 +1,1194 @@
+///
+/// Compilation of a for-program into a first-order interpretation,
+/// chaining the labelling of the print statements, the generation
+/// of the order formulas and the fitting of the interpretation.
+///
+use crate::ast::{
+    breaks_loop, collect_bound, remap_bexpr_with_map, rename_stmt, Bexpr, Program, Stmt,
+    MAX_UNROLLED,
+};
+use crate::composition::{compose, ComposeError};
+use crate::flags::{eliminate_breaks, eliminate_flags, FlagError};
+use crate::label::traverse_and_label;
+use crate::lexer::LENGTH_VARIABLE;
+use crate::order::generate_order_formula;
+use crate::qf_interpretation::{fit_interpretation, FoInterpretation};
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompileError {
+    Flag(FlagError),
+    /// The constant loops and the repetitions make more copies of a
+    /// statement than `MAX_UNROLLED`
+    Unrolled(usize),
+    Compose(ComposeError),
+}
+
+impl From<FlagError> for CompileError {
+    fn from(error: FlagError) -> Self {
+        CompileError::Flag(error)
+    }
+}
+
+impl From<ComposeError> for CompileError {
+    fn from(error: ComposeError) -> Self {
+        CompileError::Compose(error)
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompileError::Flag(error) => write!(f, "{}", error),
+            CompileError::Unrolled(copies) => write!(
+                f,
+                "the nested constant loops and repetitions make {} copies of a statement, \
+                 at most {} are unrolled",
+                copies, MAX_UNROLLED
+            ),
+            CompileError::Compose(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+pub fn compile(program: &Program) -> Result<FoInterpretation, CompileError> {
+    // Unroll the constant loops, then replace the breaks by boolean
+    // variables, and the reads of boolean variables by formulas on the
+    // loop variables
+    let stmts = eliminate_flags(&eliminate_breaks(&unroll(&program.stmts)?))?;
+
+    let mut path = Vec::new();
+    let mut labels = Vec::new();
+    let mut universe_formulas = Vec::new();
+    let mut for_vars = Vec::new();
+    let mut for0_or_for1 = Vec::new();
+    let mut label_formulas = Vec::new();
+
+    // Traverse the AST and label print statements and generate universe formulas and label formulas
+    traverse_and_label(
+        &stmts,
+        &mut path,
+        &mut labels,
+        None,
+        &mut universe_formulas,
+        &mut for_vars,
+        &mut for0_or_for1,
+        &mut label_formulas,
+        &program.alphabet,
+    );
+
+    // Calculate the order formulas (on the loop variables, so that
+    // prints in sequential loops do not share a variable)
+    let mut order_formulas = Vec::new();
+    generate_order_formula(&universe_formulas, &for0_or_for1, &mut order_formulas);
+
+    // Remap variable indices and update formulas, comparisons of two
+    // labels being replaced by a disjunction over the input letters
+    let mut remapped_universe_formulas = vec![];
+    for (vars, universe_formula) in &universe_formulas {
+        let universe_formula = expand_label_comparisons(universe_formula, &program.alphabet.input);
+        let (remapped_vars, remapped_formula) = remap_variables(vars, &universe_formula);
+        remapped_universe_formulas.push((remapped_vars, remapped_formula));
+    }
+
+    let mut remapped_label_formulas = vec![];
+    for (i, letter_formulas) in label_formulas.iter().enumerate() {
+        let (vars, _) = &universe_formulas[i];
+        remapped_label_formulas.push(
+            letter_formulas
+                .iter()
+                .map(|(letter, formula)| (letter.clone(), remap_label_formula(formula, vars)))
+                .collect(),
+        );
+    }
+
+    let for_vars: Vec<Vec<i32>> = remapped_universe_formulas
+        .iter()
+        .map(|(vars, _)| {
+            vars.iter()
+                .map(|var| var[1..].parse::<i32>().unwrap())
+                .collect()
+        })
+        .collect();
+
+    Ok(fit_interpretation(
+        remapped_universe_formulas,
+        order_formulas,
+        for_vars,
+        labels,
+        remapped_label_formulas,
+    ))
+}
+
+/// Compile the stages of a pipeline, each one reading the output of the
+/// previous one, into a single interpretation
+pub fn compile_pipeline(stages: &[Program]) -> Result<FoInterpretation, CompileError> {
+    let (first, rest) = stages
+        .split_first()
+        .expect("a pipeline has at least one stage");
+    let mut qf = compile(first)?;
+    for stage in rest {
+        qf = compose(&compile(stage)?, &qf)?;
+    }
+    Ok(qf)
+}
+
+/// Replace the constant loops and the repetitions by copies of their
+/// bodies. The positions of a constant loop are reached from the end of
+/// the word they are counted from by nested loops, so that the formulas
+/// only compare neighbouring positions: the outermost loop runs on the
+/// first (or last) position, and each inner loop on the successor (or
+/// predecessor) of the position of the loop around it.
+pub fn unroll(stmts: &[Stmt]) -> Result<Vec<Stmt>, CompileError> {
+    Unroller { copies: 0 }.block(stmts, 1)
+}
+
+struct Unroller {
+    // number of copies made so far, numbering the renamed variables
+    copies: usize,
+}
+
+impl Unroller {
+    // `copies` is the number of copies of the statements made by the
+    // unrolling of the enclosing loops
+    fn block(&mut self, stmts: &[Stmt], copies: usize) -> Result<Vec<Stmt>, CompileError> {
+        let mut unrolled = vec![];
+        for stmt in stmts {
+            match stmt {
+                Stmt::Repeat(count, body) => {
+                    let body = self.block(body, multiply(copies, *count)?)?;
+                    for _ in 0..*count {
+                        unrolled.extend(self.copy(&body, HashMap::new()));
+                    }
+                }
+                // a `break` would only exit one of the copies
+                Stmt::ForConst(var, positions, body) if breaks_loop(body) => {
+                    let body = self.block(body, copies)?;
+                    unrolled.extend(range_loop(var, positions, body));
+                }
+                Stmt::ForConst(var, positions, body) => {
+                    let body = self.block(body, multiply(copies, positions.len())?)?;
+                    unrolled.extend(self.chain(var, positions, &body));
+                }
+                Stmt::For0(var, body) => {
+                    unrolled.push(Stmt::For0(var.clone(), self.block(body, copies)?))
+                }
+                Stmt::For1(var, body) => {
+                    unrolled.push(Stmt::For1(var.clone(), self.block(body, copies)?))
+                }
+                Stmt::If(condition, body) => {
+                    unrolled.push(Stmt::If(condition.clone(), self.block(body, copies)?))
+                }
+                _ => unrolled.push(stmt.clone()),
+            }
+        }
+        Ok(unrolled)
+    }
+
+    // The copies of `body` on the positions, each one in the loop on its
+    // position
+    fn chain(&mut self, var: &str, positions: &[Bexpr], body: &[Stmt]) -> Vec<Stmt> {
+        let farthest = match positions.iter().map(distance).max() {
+            Some(farthest) => farthest,
+            None => return vec![],
+        };
+        let from_end = matches!(positions[0], Bexpr::Offset(_, _));
+        // whether the positions are visited away from their end of the word
+        let outward = positions.len() < 2 || distance(&positions[0]) < distance(&positions[1]);
+        let names: Vec<String> = (0..=farthest).map(|_| self.fresh(var)).collect();
+
+        let mut inner: Vec<Stmt> = vec![];
+        for here in (0..=farthest).rev() {
+            let mut stmts = vec![];
+            if positions.iter().any(|position| distance(position) == here) {
+                let renaming = HashMap::from([(var.to_string(), names[here].clone())]);
+                stmts = self.copy(body, renaming);
+            }
+            if outward {
+                stmts.extend(inner);
+            } else {
+                inner.extend(stmts);
+                stmts = inner;
+            }
+            let position = |index: usize, offset: i32| match offset {
+                0 => Box::new(Bexpr::Var(names[index].clone())),
+                _ => Box::new(Bexpr::Offset(names[index].clone(), offset)),
+            };
+            let guard = match (here, from_end) {
+                // the first position, and the last one
+                (0, false) => Bexpr::Equal(position(0, 0), Box::new(Bexpr::Number(0))),
+                (0, true) => Bexpr::Equal(
+                    position(0, 1),
+                    Box::new(Bexpr::Var(LENGTH_VARIABLE.to_string())),
+                ),
+                // the successor of the previous position, or its predecessor
+                (_, false) => Bexpr::Equal(position(here - 1, 1), position(here, 0)),
+                (_, true) => Bexpr::Equal(position(here, 1), position(here - 1, 0)),
+            };
+            inner = vec![Stmt::For0(
+                names[here].clone(),
+                vec![Stmt::If(guard, stmts)],
+            )];
+        }
+        inner
+    }
+
+    // Copy of statements where the variables they bind get a new name,
+    // and the other variables are renamed by `renaming`
+    fn copy(&mut self, stmts: &[Stmt], mut renaming: HashMap<String, String>) -> Vec<Stmt> {
+        let mut bound = Vec::new();
+        collect_bound(stmts, &mut bound);
+        for name in bound {
+            let renamed = self.fresh(&name);
+            renaming.insert(name, renamed);
+        }
+        stmts
+            .iter()
+            .map(|stmt| rename_stmt(stmt, &renaming))
+            .collect()
+    }
+
+    fn fresh(&mut self, name: &str) -> String {
+        self.copies += 1;
+        format!("{}_u{}", name, self.copies)
+    }
+}
+
+// The number of copies of a body repeated `count` times, in loops that
+// are already copied `copies` times
+fn multiply(copies: usize, count: usize) -> Result<usize, CompileError> {
+    match copies.checked_mul(count) {
+        Some(copies) if copies <= MAX_UNROLLED => Ok(copies),
+        _ => Err(CompileError::Unrolled(copies.saturating_mul(count))),
+    }
+}
+
+// The distance of a constant position to the end of the word it is
+// counted from: `k` is at distance `k` from the first position, `n - k`
+// at distance `k - 1` from the last one
+fn distance(position: &Bexpr) -> usize {
+    match position {
+        Bexpr::Number(k) => *k as usize,
+        Bexpr::Offset(_, k) => (-k - 1) as usize,
+        _ => unreachable!("the parser only makes constant positions"),
+    }
+}
+
+// A loop over every position, whose body only runs on the positions of
+// a constant loop
+fn range_loop(var: &str, positions: &[Bexpr], body: Vec<Stmt>) -> Option<Stmt> {
+    let offset = |position: &Bexpr| match position {
+        Bexpr::Number(k) | Bexpr::Offset(_, k) => *k,
+        _ => unreachable!("the parser only makes constant positions"),
+    };
+    let (first, last) = (positions.first()?, positions.last()?);
+    let ascending = offset(first) <= offset(last);
+    let (lower, upper) = if ascending {
+        (first, last)
+    } else {
+        (last, first)
+    };
+    let position = Box::new(Bexpr::Var(var.to_string()));
+    let guard = Bexpr::And(
+        Box::new(Bexpr::LessEqual(Box::new(lower.clone()), position.clone())),
+        Box::new(Bexpr::LessEqual(position, Box::new(upper.clone()))),
+    );
+    let body = vec![Stmt::If(guard, body)];
+    Some(if ascending {
+        Stmt::For0(var.to_string(), body)
+    } else {
+        Stmt::For1(var.to_string(), body)
+    })
+}
+
+fn remap_variables(vars: &[String], formula: &Bexpr) -> (Vec<String>, Bexpr) {
+    let mut index_map = HashMap::new();
+    let mut new_vars = vec![];
+    for (new_index, var) in vars.iter().enumerate() {
+        index_map.insert(var.clone(), format!("x{}", new_index + 1));
+        new_vars.push(format!("x{}", new_index + 1));
+    }
+    let remapped_formula = remap_bexpr_with_map(formula, &index_map);
+    (new_vars, remapped_formula)
+}
+
+// `x.label == y.label` is the disjunction of `x.label == "c" && y.label == "c"`
+// over the letters `c`, which the pullback translates to letter predicates
+fn expand_label_comparisons(expr: &Bexpr, letters: &[String]) -> Bexpr {
+    let expand = |expr: &Bexpr| Box::new(expand_label_comparisons(expr, letters));
+    match expr {
+        Bexpr::Equal(lhs, rhs) | Bexpr::NotEqual(lhs, rhs)
+            if matches!(**lhs, Bexpr::Label(_)) && matches!(**rhs, Bexpr::Label(_)) =>
+        {
+            let same_letter = letters
+                .iter()
+                .map(|letter| {
+                    let is_letter = |label: &Bexpr| {
+                        Box::new(Bexpr::Equal(
+                            Box::new(label.clone()),
+                            Box::new(Bexpr::Str(letter.clone())),
+                        ))
+                    };
+                    Bexpr::And(is_letter(lhs), is_letter(rhs))
+                })
+                .reduce(|left, right| Bexpr::Or(Box::new(left), Box::new(right)))
+                .unwrap_or(Bexpr::False);
+            match expr {
+                Bexpr::Equal(_, _) => same_letter,
+                _ => Bexpr::Not(Box::new(same_letter)),
+            }
+        }
+        Bexpr::Not(inner) => Bexpr::Not(expand(inner)),
+        Bexpr::And(lhs, rhs) => Bexpr::And(expand(lhs), expand(rhs)),
+        Bexpr::Or(lhs, rhs) => Bexpr::Or(expand(lhs), expand(rhs)),
+        Bexpr::Exists(var, inner) => Bexpr::Exists(var.clone(), expand(inner)),
+        Bexpr::Forall(var, inner) => Bexpr::Forall(var.clone(), expand(inner)),
+        _ => expr.clone(),
+    }
+}
+
+fn remap_label_formula(formula: &Bexpr, vars: &[String]) -> Bexpr {
+    match formula {
+        Bexpr::Str(s) => Bexpr::Str(remap_formula_string(s, vars)),
+        Bexpr::Or(lhs, rhs) => Bexpr::Or(
+            Box::new(remap_label_formula(lhs, vars)),
+            Box::new(remap_label_formula(rhs, vars)),
+        ),
+        _ => formula.clone(),
+    }
+}
+
+fn remap_formula_string(formula: &str, vars: &[String]) -> String {
+    // label formulas are of the form `letter(var)`, we replace the whole
+    // argument so that X1 is not substituted inside X10
+    let mut remapped_formula = formula.to_string();
+    for (new_index, var) in vars.iter().enumerate() {
+        remapped_formula =
+            remapped_formula.replace(&format!("({})", var), &format!("(x{})", new_index + 1));
+    }
+    remapped_formula
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+    use crate::lexer::{tokenize_with, Syntax};
+    use crate::qf_interpretation::evaluate;
+    use crate::qf_pullback::{bexpr_to_formula_s, pullback, FoFormula, FoFormulaR, PullbackError};
+    use crate::two_sorted_formulas::{produce_mona, produce_smtlib, ToSmtSolver};
+    use crate::{tokenize, Parser};
+
+    // Check that the compiled interpretation and the interpreter agree
+    fn assert_same_output(source: &str, words: &[&str]) {
+        let program = Parser::new(tokenize(source).unwrap()).parse().unwrap();
+        let qf = compile(&program).unwrap();
+        for word in words {
+            let expected = Interpreter::new(word).run(&program.stmts);
+            assert_eq!(
+                evaluate(&qf, word.to_string()),
+                expected,
+                "on input {:?}",
+                word
+            );
+        }
+    }
+
+    // The composed interpretation prints what the stages print when run
+    // one after the other
+    fn assert_same_pipeline_output(source: &str, words: &[&str]) {
+        let stages = Parser::new(tokenize(source).unwrap())
+            .parse_pipeline()
+            .unwrap();
+        let qf = compile_pipeline(&stages).unwrap();
+        for word in words {
+            let mut expected = word.to_string();
+            for stage in &stages {
+                expected = Interpreter::new(&expected).run(&stage.stmts);
+            }
+            assert_eq!(
+                evaluate(&qf, word.to_string()),
+                expected,
+                "on input {:?}",
+                word
+            );
+        }
+    }
+
+    // The sentence "some position carries `letter`", to pull back
+    fn some_letter(letter: &str) -> FoFormula {
+        FoFormula {
+            inside: FoFormulaR::Exists(
+                "z".into(),
+                Box::new(FoFormula {
+                    inside: FoFormulaR::PosLetter("z".into(), letter.into()),
+                }),
+            ),
+        }
+    }
+
+    // Names for the labels of an interpretation in the solver input
+    fn label_names(qf: &FoInterpretation) -> Vec<String> {
+        (0..qf.labels.len()).map(|i| format!("l{i}")).collect()
+    }
+
+    #[test]
+    fn test_examples() {
+        let words = ["", "a", "ab", "ba", "a#b", "abb#a"];
+        for file in [
+            "examples/label.txt",
+            "examples/big.txt",
+            "examples/blocks.txt",
+            "ex.txt",
+        ] {
+            let source = std::fs::read_to_string(file).unwrap();
+            assert_same_output(&source, &words);
+        }
+    }
+
+    #[test]
+    fn test_sequential_loops() {
+        let source = "for i in 0..n { print(i.label) } for j in n..0 { print(j.label) }";
+        assert_same_output(source, &["", "a", "ab", "abb#"]);
+    }
+
+    #[test]
+    fn test_concatenation() {
+        let source = r##"
+            for i in 0..n {
+                for j in 0..n {
+                    if i < j {
+                        print(i.label + "#" + j.label + "ab")
+                    }
+                }
+            }
+        "##;
+        assert_same_output(source, &["", "a", "ab", "ab#"]);
+        let program = Parser::new(tokenize(source).unwrap()).parse().unwrap();
+        let qf = compile(&program).unwrap();
+        assert_eq!(qf.labels.len(), 5);
+        assert_eq!(evaluate(&qf, "ab".to_string()), "a#bab");
+    }
+
+    #[test]
+    fn test_boolean_connectives() {
+        let source = r##"
+            for i in 0..n {
+                for j in n..0 {
+                    for k in 0..n {
+                        if (i < j && j.label == "a") || !(k == i) {
+                            print(k.label)
+                        }
+                    }
+                }
+                print("#")
+            }
+        "##;
+        assert_same_output(source, &["", "a", "ab", "ba", "aab"]);
+
+        // the conditions also go through the pullback
+        let program = Parser::new(tokenize(source).unwrap()).parse().unwrap();
+        let qf = compile(&program).unwrap();
+        let formula = pullback(&some_letter("a"), &qf).unwrap().to_smtlib();
+        assert!(formula.contains("(= (word z3) a)"));
+        assert!(!formula.contains("x3"));
+    }
+
+    #[test]
+    fn test_else_if_cascade() {
+        let source = r##"
+            for i in 0..n {
+                if i.label == "a" {
+                    print("b")
+                } else if i.label == "b" {
+                    print("a")
+                } else {
+                    print(i.label)
+                }
+                for j in n..0 {
+                    if i < j && j.label == "a" {
+                        print(j.label)
+                    } else {
+                        print("#")
+                    }
+                }
+            }
+        "##;
+        assert_same_output(source, &["", "a", "ab", "#ba"]);
+
+        let program = Parser::new(tokenize(source).unwrap()).parse().unwrap();
+        assert_eq!(
+            evaluate(&compile(&program).unwrap(), "ab".to_string()),
+            "b##a##"
+        );
+    }
+
+    #[test]
+    fn test_offsets() {
+        let source = r##"
+            for i in 0..n {
+                for j in 0..n {
+                    if j == i + 1 || (i - 2 >= j && j.label != "a") {
+                        print(j.label)
+                    }
+                }
+                print("#")
+            }
+        "##;
+        assert_same_output(source, &["", "a", "ab", "aba", "ab#ba"]);
+
+        // offsets become chains of successors in the solver formulas
+        let program = Parser::new(tokenize(source).unwrap()).parse().unwrap();
+        let qf = compile(&program).unwrap();
+        let formula = pullback(&some_letter("a"), &qf).unwrap();
+        assert!(formula.to_smtlib().contains("(= z2 (+ z1 1))"));
+        assert!(formula.to_mona().contains("z2 = z1 + 1"));
+    }
+
+    #[test]
+    fn test_quantifiers() {
+        // for each pair of positions, `#` when a separator lies strictly
+        // between them, the second letter when there is no `b` between
+        // them, and nothing otherwise
+        let source = r##"
+            for i in 0..n {
+                for j in i..n {
+                    if exists k. i < k && k < j && k.label == "#" {
+                        print("#")
+                    } else if forall k.k < i || j < k || k.label != "b" {
+                        print(j.label)
+                    }
+                }
+                if !exists k. k.label == "a" { print("b") }
+            }
+        "##;
+        assert_same_output(source, &["", "a", "b", "ab#a", "a#b#a", "bb#"]);
+        let program = Parser::new(tokenize(source).unwrap()).parse().unwrap();
+        let qf = compile(&program).unwrap();
+        assert_eq!(evaluate(&qf, "a#a".to_string()), "a###aa");
+
+        // the nested quantifiers go through the pullback
+        let formula = pullback(&some_letter("a"), &qf).unwrap();
+        let labels = label_names(&qf);
+        let alphabet = &program.alphabet.input;
+        let smtlib = produce_smtlib(&formula, alphabet, &labels);
+        assert!(smtlib.contains("forall") && smtlib.contains("exists"));
+        assert!(!produce_mona(&formula, alphabet, &labels).is_empty());
+    }
+
+    #[test]
+    fn test_break() {
+        // the letters up to the next `#` after each position, the last
+        // `a`, and the first three letters up to the first `#`
+        let source = r##"
+            for i in 0..n {
+                for j in i..n {
+                    if j.label == "#" { break }
+                    print(j.label)
+                }
+                print("#")
+            }
+            for i in n..0 {
+                if i.label == "a" {
+                    print("a")
+                    break
+                    print("b")
+                }
+            }
+            for k in 0..3 {
+                if k.label == "#" { break }
+                print(k.label)
+            }
+        "##;
+        assert_same_output(source, &["", "a", "#", "b#a", "ab#ba", "abba#"]);
+        let program = Parser::new(tokenize(source).unwrap()).parse().unwrap();
+        assert_eq!(
+            evaluate(&compile(&program).unwrap(), "ab#ba".to_string()),
+            "ab#b##ba#a#aab"
+        );
+    }
+
+    #[test]
+    fn test_paper_syntax() {
+        let parse_paper = |source: &str| {
+            let tokens = tokenize_with(source, Syntax::Paper).unwrap();
+            Parser::new(tokens).parse_paper().unwrap()
+        };
+        let paper = r##"
+            initialize b
+            for i in stdin -> do
+              for j in stdin <- do
+                if i < j /\ !b then
+                  print(j.label + "#")
+                else
+                  skip
+                end
+                if j == i + 1 \/ j.label == "#" then b = True end
+              end
+            end
+        "##;
+        let braces = r##"
+            var b := False
+            for i in 0..n {
+                for j in n..0 {
+                    if i < j && !b { print(j.label + "#") }
+                    if j == i + 1 || j.label == "#" { b := True }
+                }
+            }
+        "##;
+        let words = ["", "a", "ab", "a#b", "abba"];
+        let program = parse_paper(paper);
+        let qf = compile(&program).unwrap();
+        let expected = Parser::new(tokenize(braces).unwrap()).parse().unwrap();
+        for word in words {
+            let output = Interpreter::new(word).run(&expected.stmts);
+            assert_eq!(Interpreter::new(word).run(&program.stmts), output);
+            assert_eq!(
+                evaluate(&qf, word.to_string()),
+                output,
+                "on input {:?}",
+                word
+            );
+        }
+
+        // the examples of semantics.md
+        for file in ["examples/reverse.for", "examples/cyclic_permutation.for"] {
+            let program = parse_paper(&std::fs::read_to_string(file).unwrap());
+            let qf = compile(&program).unwrap();
+            for word in words {
+                let output = Interpreter::new(word).run(&program.stmts);
+                assert_eq!(
+                    evaluate(&qf, word.to_string()),
+                    output,
+                    "on input {:?}",
+                    word
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_variable_ranges() {
+        // the suffix and the prefix of every position, separated by `#`
+        let source = r##"
+            for i in 0..n {
+                for j in i..n { print(j.label) }
+                print("#")
+                for j in i..=0 { print(j.label) }
+                print("#")
+            }
+        "##;
+        assert_same_output(source, &["", "a", "ab", "abb#"]);
+        let program = Parser::new(tokenize(source).unwrap()).parse().unwrap();
+        assert_eq!(Interpreter::new("ab").run(&program.stmts), "ab#a#b#ba#");
+
+        // the positions strictly between two others, with offsets
+        let source = r##"
+            for i in 0..n {
+                for k in n - 1..i + 1 {
+                    if i.label == k.label { print("#") }
+                    for j in 1..k { print(j.label) }
+                    for j in k..=i { print(j.label) }
+                }
+            }
+        "##;
+        assert_same_output(source, &["", "a", "ab", "aab", "ab#ba"]);
+    }
+
+    #[test]
+    fn test_constant_loops() {
+        // the first three and the last two positions, or fewer on short words
+        let source = r##"
+            for i in 0..3 { print(i.label) }
+            print("#")
+            for i in 2..=0 { print(i.label) }
+            print("#")
+            for i in n..n - 2 {
+                for j in n - 2..n {
+                    if i == j { print("#") } else { print(j.label) }
+                }
+            }
+        "##;
+        assert_same_output(source, &["", "a", "ab", "aab", "ab#ba"]);
+        let program = Parser::new(tokenize(source).unwrap()).parse().unwrap();
+        assert_eq!(Interpreter::new("ab").run(&program.stmts), "ab#ba#a##b");
+        assert_eq!(Interpreter::new("abab").run(&program.stmts), "aba#aba#a##b");
+
+        // repetitions do not depend on the input
+        let source = r##"
+            repeat 3 {
+                var seen := False
+                for i in 0..n {
+                    if seen { print(i.label) } else if i.label == "b" { seen := True }
+                }
+                print("#")
+            }
+        "##;
+        assert_same_output(source, &["", "a", "ab", "bab", "abba"]);
+        let program = Parser::new(tokenize(source).unwrap()).parse().unwrap();
+        assert_eq!(Interpreter::new("").run(&program.stmts), "###");
+        assert_eq!(Interpreter::new("bab").run(&program.stmts), "ab#ab#ab#");
+
+        // a break exits the whole loop, which is not unrolled
+        let source = r##"
+            for i in 1..=3 {
+                if i.label == "#" { break }
+                print(i.label)
+            }
+            repeat 2 {
+                for i in n..n - 3 {
+                    if i.label == "#" { break }
+                    print(i.label)
+                }
+            }
+        "##;
+        assert_same_output(source, &["", "a", "ab#b", "aab", "ab#ba"]);
+
+        // the copies of nested loops are counted together
+        let source = "repeat 4 { for i in 0..8 { print(i.label) } }";
+        let program = Parser::new(tokenize(source).unwrap()).parse().unwrap();
+        assert_eq!(compile(&program).err(), Some(CompileError::Unrolled(32)));
+    }
+
+    #[test]
+    fn test_procedures() {
+        // the same names inside and outside of the procedure, and two calls
+        // in the same loop, must not be confused
+        let source = r##"
+            proc emit_suffix(i) {
+                var done := False
+                for m in 0..n {
+                    if m >= i && !done { print(m.label) }
+                    if m.label == "#" { done := True }
+                }
+            }
+            proc emit_between(i, j) {
+                for m in 0..n {
+                    if i < m && m < j { emit_suffix(m) }
+                }
+            }
+            for m in n..0 {
+                emit_suffix(m)
+                print("#")
+                for i in 0..n { emit_between(i, m) }
+            }
+        "##;
+        assert_same_output(source, &["", "a", "ab", "ab#b", "abba"]);
+        let program = Parser::new(tokenize(source).unwrap()).parse().unwrap();
+        assert_eq!(Interpreter::new("abb").run(&program.stmts), "b#bbbb#abb#");
+    }
+
+    #[test]
+    fn test_blocks() {
+        let source = r##"
+            for b in blocks(stdin, "#") {
+                for i in rev(b) { print(i.label) }
+                print("#")
+            }
+            for b in rev(blocks(stdin, "#")) {
+                for i in b {
+                    if b < i { print(i.label) }
+                }
+                print("a")
+            }
+        "##;
+        assert_same_output(source, &["", "#", "a#", "b##a", "ab#ba##b"]);
+        let program = Parser::new(tokenize(source).unwrap()).parse().unwrap();
+        assert_eq!(
+            Interpreter::new("ab#ba##b").run(&program.stmts),
+            "ba#ab##b#aaaaba"
+        );
+    }
+
+    #[test]
+    fn test_composition() {
+        let source = r##"
+            transducer reverse {
+                for i in n..0 { print(i.label) }
+            }
+            transducer square {
+                for i in 0..n {
+                    for j in 0..n {
+                        if i == j { print("#") } else { print(j.label) }
+                    }
+                }
+            }
+            main = reverse . square
+        "##;
+        assert_same_pipeline_output(source, &["", "a", "ab", "aba"]);
+
+        // offsets, constants, label comparisons and flags in the outer
+        // transducer are evaluated on the output of the inner one
+        let source = r##"
+            alphabet { input "ab" output "ab#" }
+            transducer double {
+                alphabet { input "ab" output "ab" }
+                for i in 0..n { print(i.label) print(i.label) }
+            }
+            transducer mark {
+                if n == 0 { print("#") }
+                for i in 0..n {
+                    var seen := False
+                    for j in 0..n {
+                        if j < i && j.label == i.label { seen := True }
+                    }
+                    if first(i) || i + 1 == n - 1 { print("#") }
+                    if seen { print(i.label) }
+                }
+            }
+            main = mark . double
+        "##;
+        assert_same_pipeline_output(source, &["", "a", "ab", "abb"]);
+
+        // a third stage is composed with the composition of the first two
+        let source = r##"
+            transducer reverse { for i in n..0 { print(i.label) } }
+            transducer shift {
+                for i in 0..n { for j in 0..n { if j == i + 1 { print(j.label) } } }
+                print("#")
+            }
+            main = shift . reverse . shift
+        "##;
+        assert_same_pipeline_output(source, &["", "a", "ab", "abb"]);
+
+        // the composition is a first order interpretation whose output
+        // positions are tuples of the positions of the inner stages, two
+        // for each `shift` and one for `reverse`
+        let stages = Parser::new(tokenize(source).unwrap())
+            .parse_pipeline()
+            .unwrap();
+        let qf = compile_pipeline(&stages).unwrap();
+        let formula = pullback(&some_letter("a"), &qf).unwrap().to_smtlib();
+        assert!(formula.contains("(exists ((z4 Int))"));
+        assert!(formula.contains("(= (word z4) a)"));
+        assert!(!formula.contains("z5"));
+
+        // the formulas grow with each stage, the evaluation only stays
+        // fast on short words because it skips the quantifiers guarded by
+        // a false universe formula
+        let source = r##"
+            transducer double { for i in 0..n { print(i.label) print(i.label) } }
+            transducer shift {
+                for i in 0..n { for j in 0..n { if j == i + 1 { print(j.label) } } }
+                print("#")
+            }
+            transducer mark {
+                for i in 0..n {
+                    var seen := False
+                    for j in 0..n {
+                        if j < i && j.label == i.label { seen := True }
+                    }
+                    if first(i) { print("#") }
+                    if seen { print(i.label) }
+                }
+            }
+            main = mark . shift . double
+        "##;
+        assert_same_pipeline_output(source, &["", "a", "ab", "abb"]);
+    }
+
+    #[test]
+    fn test_constant_positions() {
+        let source = r##"
+            for i in 0..n {
+                if first(i) || last(i) {
+                    print(i.label)
+                } else if 2 <= i && i < n - 1 {
+                    print("#")
+                }
+                if i == 1 || n - 2 == 0 {
+                    print("a")
+                }
+            }
+            if n - 1 < 2 {
+                print("b")
+            }
+        "##;
+        assert_same_output(source, &["", "a", "ab", "aba", "abba", "ab#ba"]);
+
+        let program = Parser::new(tokenize(source).unwrap()).parse().unwrap();
+        let qf = compile(&program).unwrap();
+        assert_eq!(evaluate(&qf, "abbab".to_string()), "aa##b");
+
+        // constants are counted from the first position, `n` from the last
+        let formula = pullback(&some_letter("a"), &qf).unwrap().to_smtlib();
+        assert!(formula.contains("pos_first"));
+        assert!(formula.contains("pos_last"));
+    }
+
+    #[test]
+    fn test_label_comparisons() {
+        let source = r##"
+            for i in 0..n {
+                for j in 0..n {
+                    if i < j && i.label == j.label {
+                        print(j.label)
+                    }
+                    if j == i + 1 && i.label != j.label {
+                        print("#")
+                    }
+                }
+            }
+        "##;
+        assert_same_output(source, &["", "a", "aa", "ab", "abab", "a#ba#"]);
+        let program = Parser::new(tokenize(source).unwrap()).parse().unwrap();
+        let qf = compile(&program).unwrap();
+        assert_eq!(evaluate(&qf, "abab".to_string()), "#a#b#");
+
+        // the pullback only translates the expanded comparisons
+        let label = |var: &str| Box::new(Bexpr::Label(var.into()));
+        let comparison = Bexpr::Equal(label("x1"), label("x2"));
+        assert_eq!(
+            bexpr_to_formula_s(&comparison).err(),
+            Some(PullbackError::Comparison("(x1 == x2)".into()))
+        );
+        assert!(bexpr_to_formula_s(&expand_label_comparisons(&comparison, &["a".into()])).is_ok());
+    }
+
+    #[test]
+    fn test_letter_classes() {
+        let source = r##"
+            alphabet {
+                input "abc#"
+            }
+            class separator = {"#", "c"}
+            for i in 0..n {
+                if i.label in separator {
+                    print("#")
+                } else if !(i.label in {"a"}) {
+                    print("c")
+                } else {
+                    print(i.label)
+                }
+            }
+        "##;
+        assert_same_output(source, &["", "a", "abc", "c#ba"]);
+        let program = Parser::new(tokenize(source).unwrap()).parse().unwrap();
+        let qf = compile(&program).unwrap();
+        assert_eq!(evaluate(&qf, "ab#c".to_string()), "ac##");
+
+        // membership is a disjunction of letter predicates
+        let formula = pullback(&some_letter("a"), &qf).unwrap().to_smtlib();
+        assert!(formula.contains("(or (= (word z1) |#|)  (= (word z1) c) )"));
+    }
+
+    #[test]
+    fn test_letter_maps() {
+        let source = r##"
+            alphabet {
+                input "ab#"
+                output "ab#AB"
+            }
+            map swap = {a -> b, b -> a}
+            map upper = {a -> A, b -> B, "#" -> a}
+            for i in 0..n {
+                print(swap[i.label])
+                for j in n..0 {
+                    if j == i + 1 {
+                        print(upper[j.label])
+                    }
+                }
+            }
+        "##;
+        assert_same_output(source, &["", "a", "ab", "b#a#"]);
+        let program = Parser::new(tokenize(source).unwrap()).parse().unwrap();
+        let qf = compile(&program).unwrap();
+        assert_eq!(evaluate(&qf, "ab#".to_string()), "bBaa#");
+    }
+
+    #[test]
+    fn test_alphabet() {
+        let source = r##"
+            alphabet {
+                input "()T"
+                output "()T."
+            }
+            for i in 0..n {
+                if i.label == "(" {
+                    print(")")
+                } else if i.label == "T" {
+                    print(".")
+                } else {
+                    print(i.label)
+                }
+            }
+        "##;
+        assert_same_output(source, &["", "(", "()T", "T)(("]);
+        let program = Parser::new(tokenize(source).unwrap()).parse().unwrap();
+        let qf = compile(&program).unwrap();
+        assert_eq!(evaluate(&qf, "(T)".to_string()), ").)");
+
+        // letters that are not symbols are quoted in the solver input
+        let formula = pullback(&some_letter(")"), &qf).unwrap();
+        let labels = label_names(&qf);
+        let smtlib = produce_smtlib(&formula, &program.alphabet.input, &labels);
+        assert!(smtlib.contains("(declare-datatype Letter ((blank) (|(|) (|)|) (T)))"));
+        assert!(smtlib.contains("(= (word z1) |)|)"));
+        assert!(produce_mona(&formula, &program.alphabet.input, &labels).contains("L_u29_"));
+    }
+
+    #[test]
+    fn test_symbols() {
+        let source = r##"
+            alphabet {
+                input {"open", "close", "x"}
+                output {"open", "close", "x", "()"}
+            }
+            for i in 0..n {
+                if i.label == "open" {
+                    for j in i..n {
+                        if j.label == "close" {
+                            print("()")
+                        }
+                    }
+                } else {
+                    print(i.label)
+                }
+            }
+        "##;
+        let program = Parser::new(tokenize(source).unwrap()).parse().unwrap();
+        let qf = compile(&program).unwrap();
+        for word in ["", "x", "open close", "open,x,close,close"] {
+            let letters = program.alphabet.split_word(word).unwrap();
+            let expected = Interpreter::with_letters(letters.clone()).run_letters(&program.stmts);
+            assert_eq!(
+                crate::qf_interpretation::evaluate_word(&qf, &letters),
+                expected,
+                "on input {:?}",
+                word
+            );
+        }
+        assert_eq!(
+            Interpreter::with_letters(program.alphabet.split_word("open x close").unwrap())
+                .run(&program.stmts),
+            "() x close"
+        );
+        assert!(program.alphabet.split_word("open,y").is_err());
+
+        // the symbols are constructors of the Letter datatype
+        let formula = pullback(&some_letter("open"), &qf).unwrap();
+        let labels = label_names(&qf);
+        let smtlib = produce_smtlib(&formula, &program.alphabet.input, &labels);
+        assert!(
+            smtlib.contains("(declare-datatype Letter ((blank) (letter_open) (letter_close) (x)))")
+        );
+        assert!(smtlib.contains("(= (word z1) letter_open)"));
+    }
+
+    #[test]
+    fn test_input_modes() {
+        let program = |source: &str| Parser::new(tokenize(source).unwrap()).parse().unwrap();
+        let assert_same_letters = |program: &Program, words: &[&str]| {
+            let qf = compile(program).unwrap();
+            for word in words {
+                let letters = program.alphabet.split_word(word).unwrap();
+                let expected =
+                    Interpreter::with_letters(letters.clone()).run_letters(&program.stmts);
+                let output = crate::qf_interpretation::evaluate_word(&qf, &letters);
+                assert_eq!(output, expected, "on input {:?}", word);
+            }
+        };
+        let source = "
+            alphabet { input \"aé\u{301}\" output \"aé\u{301}#\" }
+            for i in n..0 {
+                if i.label == \"a\" {
+                    print(\"#\")
+                } else {
+                    print(i.label)
+                }
+            }
+        ";
+        assert_same_output(source, &["", "é", "aé", "éé\u{301}a"]);
+        let qf = compile(&program(source)).unwrap();
+        assert_eq!(evaluate(&qf, "aéa".to_string()), "#é#");
+
+        // a grapheme cluster is one position
+        let graphemes = source.replace("alphabet {", "alphabet { mode graphemes");
+        let graphemes = program(&graphemes.replace("aé\u{301}", "ae\u{301}"));
+        assert_eq!(graphemes.alphabet.input, ["a", "e\u{301}"]);
+        assert_same_letters(&graphemes, &["", "e\u{301}", "ae\u{301}ae\u{301}"]);
+        let letters = graphemes.alphabet.split_word("ae\u{301}").unwrap();
+        let output = Interpreter::with_letters(letters).run_letters(&graphemes.stmts);
+        assert_eq!(graphemes.alphabet.join_word(&output), "e\u{301}#");
+
+        // and a byte is one position, the other bytes being escaped
+        let bytes = program(&source.replace("alphabet {", "alphabet { mode bytes"));
+        assert_same_letters(&bytes, &["", "é", "aéa"]);
+        let letters = bytes.alphabet.split_word("aé").unwrap();
+        let output = Interpreter::with_letters(letters).run_letters(&bytes.stmts);
+        assert_eq!(output, ["\\xa9", "\\xc3", "#"]);
+
+        // the escapes are quoted in the solver input
+        let qf = compile(&bytes).unwrap();
+        let formula = pullback(&some_letter("\\xc3"), &qf).unwrap();
+        let labels = label_names(&qf);
+        let smtlib = produce_smtlib(&formula, &bytes.alphabet.input, &labels);
+        assert!(smtlib.contains("(= (word z1) |letter__u5c_xc3|)"));
+        assert!(produce_mona(&formula, &bytes.alphabet.input, &labels).contains("_u5c_xc3"));
+    }
+
+    #[test]
+    fn test_boolean_variables() {
+        let source = std::fs::read_to_string("examples/cyclic.txt").unwrap();
+        assert_same_output(&source, &["", "a", "ab", "abb", "ab#a"]);
+        let program = Parser::new(tokenize(&source).unwrap()).parse().unwrap();
+        let qf = compile(&program).unwrap();
+        assert_eq!(evaluate(&qf, "aab".to_string()), "baa");
+
+        // the value of the flag is given by a quantified formula
+        assert!(pullback(&some_letter("a"), &qf)
+            .unwrap()
+            .to_smtlib()
+            .contains("exists"));
+
+        // a flag set to both values, and read by an else cascade whose
+        // branches assign it
+        let source = r##"
+            alphabet {
+                input "ab#"
+                output "ab#?"
+            }
+            for i in 0..n {
+                var b := False
+                for j in 0..n {
+                    if j.label == "a" {
+                        b := True
+                    } else if j.label == "b" && j < i {
+                        b := False
+                    }
+                }
+                if b {
+                    print(i.label)
+                    b := False
+                } else if !b {
+                    print("#")
+                    b := True
+                } else {
+                    print("?")
+                }
+            }
+        "##;
+        assert_same_output(source, &["", "a", "ab", "ba", "abba", "#a#b"]);
+
+        // the parity of the length is not first order definable
+        let source = r##"
+            var even := True
+            for i in 0..n {
+                if even {
+                    even := False
+                } else {
+                    even := True
+                }
+            }
+            if even {
+                print("#")
+            }
+        "##;
+        let program = Parser::new(tokenize(source).unwrap()).parse().unwrap();
+        assert_eq!(
+            compile(&program).err(),
+            Some(CompileError::Flag(FlagError::Cyclic("X1".to_string())))
+        );
+    }
+}
@@ -0,0 +1,615 @@
+///
+/// Composition of interpretations. An output position of the composed
+/// interpretation is an output position of the outer interpretation
+/// whose variables are output positions of the inner one. The formulas
+/// of the outer interpretation are rewritten on the input positions of
+/// the inner one: comparisons of positions become inner order formulas,
+/// letters become inner letter formulas, and quantifiers range over the
+/// output positions of the inner interpretation.
+///
+use crate::ast::Bexpr;
+use crate::flags::{and, exists, or};
+use crate::lexer::LENGTH_VARIABLE;
+use crate::qf_interpretation::{FoInterpretation, Label};
+use crate::qf_pullback::parse_letter_at_pos;
+use itertools::Itertools;
+use std::collections::HashMap;
+use std::fmt;
+
+/// An output position of the inner interpretation: its label and the
+/// variables holding its input positions
+#[derive(Debug, Clone)]
+struct InnerPosition {
+    label: Label,
+    vars: Vec<String>,
+}
+
+/// The first or the last output position of the inner interpretation,
+/// to which constants and `n` are relative
+#[derive(Debug, Clone, Copy)]
+enum Anchor {
+    First,
+    Last,
+}
+
+#[derive(Debug, Clone)]
+enum Side {
+    At(InnerPosition),
+    Anchor(Anchor),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Relation {
+    LessEqual,
+    Less,
+    Equal,
+    NotEqual,
+    GreaterEqual,
+    Greater,
+}
+
+impl Relation {
+    fn holds(self, left: i32, right: i32) -> bool {
+        match self {
+            Relation::LessEqual => left <= right,
+            Relation::Less => left < right,
+            Relation::Equal => left == right,
+            Relation::NotEqual => left != right,
+            Relation::GreaterEqual => left >= right,
+            Relation::Greater => left > right,
+        }
+    }
+}
+
+/// A formula of the outer interpretation that is not on its output
+/// positions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ComposeError {
+    /// A variable that is neither a free variable of the formula nor
+    /// quantified by it
+    Unbound(String),
+    /// An expression that is not a formula on positions and letters
+    Unexpected(String),
+}
+
+impl fmt::Display for ComposeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ComposeError::Unbound(var) => {
+                write!(f, "variable {} is not bound in the composed formula", var)
+            }
+            ComposeError::Unexpected(expr) => {
+                write!(f, "unexpected expression {} in a composed formula", expr)
+            }
+        }
+    }
+}
+
+/// Prefix of the variables quantified over the input positions of the
+/// inner output positions (the free variables are `x1`, `y1`, ... and the
+/// variables quantified by the compilation of flags `Q1`, `Q2`, ...)
+const BOUND_PREFIX: &str = "P";
+
+/// The interpretation running `inner` and then `outer` on its output.
+/// Every quantifier of the outer formulas becomes a disjunction over the
+/// labels of the inner interpretation, so the formulas grow with each
+/// stage of a pipeline.
+pub fn compose(
+    outer: &FoInterpretation,
+    inner: &FoInterpretation,
+) -> Result<FoInterpretation, ComposeError> {
+    // the inner formulas may come from a composition already, the new
+    // quantified variables must not be captured by theirs
+    let fresh = inner
+        .universe
+        .iter()
+        .map(|(_, formula)| formula)
+        .chain(inner.order.iter().map(|(_, _, formula)| formula))
+        .chain(inner.letters.iter().map(|(_, _, formula)| formula))
+        .map(last_bound)
+        .max()
+        .unwrap_or(0);
+    let mut composer = Composer { inner, fresh };
+
+    // one label for every outer label and inner labels of its variables
+    let labels: Vec<(Label, Vec<Label>)> = outer
+        .arities
+        .iter()
+        .enumerate()
+        .flat_map(|(label, &arity)| {
+            std::iter::repeat_n(0..inner.arities.len(), arity)
+                .multi_cartesian_product()
+                .map(move |inner_labels| (label, inner_labels))
+        })
+        .collect();
+
+    let mut qf = FoInterpretation::new();
+    for (index, (label, inner_labels)) in labels.iter().enumerate() {
+        let inner_names: Vec<String> = inner_labels
+            .iter()
+            .map(|inner_label| inner.labels[*inner_label].clone())
+            .collect();
+        qf.labels.push(format!(
+            "{}({})",
+            outer.labels[*label],
+            inner_names.join(", ")
+        ));
+        qf.arities.push(
+            inner_labels
+                .iter()
+                .map(|inner_label| inner.arities[*inner_label])
+                .sum(),
+        );
+
+        // the variables are inner positions, which must exist
+        let environment = composer.environment(inner_labels, "x");
+        let mut universe = Bexpr::True;
+        for i in 1..=inner_labels.len() {
+            universe = and(
+                universe,
+                composer.universe(&environment[&format!("x{}", i)]),
+            );
+        }
+        if let Some((_, formula)) = outer.universe.iter().find(|(l, _)| l == label) {
+            universe = and(universe, composer.translate(formula, &environment)?);
+        }
+        qf.universe.push((index, universe));
+
+        for (outer_label, letter, formula) in &outer.letters {
+            if outer_label == label {
+                let formula = composer.translate(formula, &environment)?;
+                qf.letters.push((index, letter.clone(), formula));
+            }
+        }
+    }
+
+    for (index_x, (label_x, inner_labels_x)) in labels.iter().enumerate() {
+        for (index_y, (label_y, inner_labels_y)) in labels.iter().enumerate() {
+            let mut environment = composer.environment(inner_labels_x, "x");
+            environment.extend(composer.environment(inner_labels_y, "y"));
+            let formula = match outer
+                .order
+                .iter()
+                .find(|(l_x, l_y, _)| l_x == label_x && l_y == label_y)
+            {
+                Some((_, _, formula)) => composer.translate(formula, &environment)?,
+                None => Bexpr::False,
+            };
+            qf.order.push((index_x, index_y, formula));
+        }
+    }
+    Ok(qf)
+}
+
+struct Composer<'a> {
+    inner: &'a FoInterpretation,
+    fresh: usize,
+}
+
+impl Composer<'_> {
+    // The inner positions of the variables `prefix1`, `prefix2`, ... of an
+    // outer position, whose input positions are numbered in sequence
+    fn environment(&self, inner_labels: &[Label], prefix: &str) -> HashMap<String, InnerPosition> {
+        let mut next = 0;
+        let mut environment = HashMap::new();
+        for (i, &label) in inner_labels.iter().enumerate() {
+            let arity = self.inner.arities[label];
+            let vars = (next + 1..=next + arity)
+                .map(|k| format!("{}{}", prefix, k))
+                .collect();
+            next += arity;
+            environment.insert(
+                format!("{}{}", prefix, i + 1),
+                InnerPosition { label, vars },
+            );
+        }
+        environment
+    }
+
+    // Rewrite a formula of the outer interpretation on inner positions
+    fn translate(
+        &mut self,
+        formula: &Bexpr,
+        environment: &HashMap<String, InnerPosition>,
+    ) -> Result<Bexpr, ComposeError> {
+        let position = |var: &String| match environment.get(var) {
+            Some(position) => Ok(position.clone()),
+            None => Err(ComposeError::Unbound(var.clone())),
+        };
+        Ok(match formula {
+            Bexpr::True | Bexpr::False => formula.clone(),
+            Bexpr::Str(s) => match parse_letter_at_pos(s) {
+                Some((letter, var)) => self.letter(&letter, &position(&var)?),
+                None => return Err(ComposeError::Unexpected(formula.to_string())),
+            },
+            Bexpr::Not(inner) => self.translate(inner, environment)?.negated(),
+            Bexpr::And(left, right) => {
+                let left = self.translate(left, environment)?;
+                and(left, self.translate(right, environment)?)
+            }
+            Bexpr::Or(left, right) => {
+                let left = self.translate(left, environment)?;
+                or(left, self.translate(right, environment)?)
+            }
+            Bexpr::Exists(var, inner) => self.exists_position(&mut |composer, position| {
+                let mut environment = environment.clone();
+                environment.insert(var.clone(), position);
+                composer.translate(inner, &environment)
+            })?,
+            Bexpr::Forall(var, inner) => self
+                .exists_position(&mut |composer, position| {
+                    let mut environment = environment.clone();
+                    environment.insert(var.clone(), position);
+                    Ok(composer.translate(inner, &environment)?.negated())
+                })?
+                .negated(),
+            Bexpr::LetterIn(var, letters) => {
+                let position = position(var)?;
+                letters
+                    .iter()
+                    .map(|letter| self.letter(letter, &position))
+                    .fold(Bexpr::False, or)
+            }
+            Bexpr::Equal(left, right) | Bexpr::NotEqual(left, right)
+                if matches!(**left, Bexpr::Label(_)) || matches!(**right, Bexpr::Label(_)) =>
+            {
+                let same = match (&**left, &**right) {
+                    (Bexpr::Label(var), Bexpr::Str(letter))
+                    | (Bexpr::Str(letter), Bexpr::Label(var)) => {
+                        self.letter(letter, &position(var)?)
+                    }
+                    (Bexpr::Label(left), Bexpr::Label(right)) => {
+                        let (left, right) = (position(left)?, position(right)?);
+                        let letters: Vec<String> = self
+                            .inner
+                            .letters
+                            .iter()
+                            .map(|(_, letter, _)| letter.clone())
+                            .unique()
+                            .collect();
+                        letters
+                            .iter()
+                            .map(|letter| {
+                                and(self.letter(letter, &left), self.letter(letter, &right))
+                            })
+                            .fold(Bexpr::False, or)
+                    }
+                    _ => return Err(ComposeError::Unexpected(formula.to_string())),
+                };
+                match formula {
+                    Bexpr::Equal(_, _) => same,
+                    _ => same.negated(),
+                }
+            }
+            Bexpr::LessEqual(left, right) => {
+                self.comparison(Relation::LessEqual, left, right, environment)?
+            }
+            Bexpr::Less(left, right) => {
+                self.comparison(Relation::Less, left, right, environment)?
+            }
+            Bexpr::Equal(left, right) => {
+                self.comparison(Relation::Equal, left, right, environment)?
+            }
+            Bexpr::NotEqual(left, right) => {
+                self.comparison(Relation::NotEqual, left, right, environment)?
+            }
+            Bexpr::GreaterEqual(left, right) => {
+                self.comparison(Relation::GreaterEqual, left, right, environment)?
+            }
+            Bexpr::Greater(left, right) => {
+                self.comparison(Relation::Greater, left, right, environment)?
+            }
+            _ => return Err(ComposeError::Unexpected(formula.to_string())),
+        })
+    }
+
+    // Translate `left relation right` on positions, with offsets and
+    // constants, where a constant `k` is the first position plus `k` and
+    // `n + c` is the last position plus `c + 1`
+    fn comparison(
+        &mut self,
+        relation: Relation,
+        left: &Bexpr,
+        right: &Bexpr,
+        environment: &HashMap<String, InnerPosition>,
+    ) -> Result<Bexpr, ComposeError> {
+        let term = |expr: &Bexpr| match expr {
+            Bexpr::Number(k) => Ok((None, Some(Anchor::First), *k)),
+            Bexpr::Var(name) if name == LENGTH_VARIABLE => Ok((None, Some(Anchor::Last), 1)),
+            Bexpr::Offset(name, k) if name == LENGTH_VARIABLE => {
+                Ok((None, Some(Anchor::Last), k + 1))
+            }
+            Bexpr::Var(name) => Ok((Some(name.clone()), None, 0)),
+            Bexpr::Offset(name, k) => Ok((Some(name.clone()), None, *k)),
+            _ => Err(ComposeError::Unexpected(expr.to_string())),
+        };
+        let (left_var, left_anchor, left_offset) = term(left)?;
+        let (right_var, right_anchor, right_offset) = term(right)?;
+
+        // a variable compared with itself only depends on the offsets
+        if left_var.is_some() && left_var == right_var {
+            let holds = relation.holds(left_offset, right_offset);
+            return Ok(if holds { Bexpr::True } else { Bexpr::False });
+        }
+
+        let side = |var: Option<String>, anchor: Option<Anchor>| match (var, anchor) {
+            (Some(var), _) => match environment.get(&var) {
+                Some(position) => Ok(Side::At(position.clone())),
+                None => Err(ComposeError::Unbound(var)),
+            },
+            (None, Some(anchor)) => Ok(Side::Anchor(anchor)),
+            (None, None) => unreachable!(),
+        };
+        let formula = self.compare(
+            relation,
+            side(left_var, left_anchor)?,
+            left_offset,
+            side(right_var, right_anchor)?,
+            right_offset,
+        )?;
+
+        // without variables, the comparison is decided with `n = 0` on an
+        // empty inner output, which has no first or last position
+        match (left_anchor, right_anchor) {
+            (Some(left_anchor), Some(right_anchor)) => {
+                let on_empty = |anchor: Anchor, offset: i32| match anchor {
+                    Anchor::First => offset,
+                    Anchor::Last => offset - 1,
+                };
+                if relation.holds(
+                    on_empty(left_anchor, left_offset),
+                    on_empty(right_anchor, right_offset),
+                ) {
+                    let empty = self.exists_position(&mut |_, _| Ok(Bexpr::True))?;
+                    Ok(or(formula, empty.negated()))
+                } else {
+                    Ok(formula)
+                }
+            }
+            _ => Ok(formula),
+        }
+    }
+
+    // `left + left_offset relation right + right_offset`
+    fn compare(
+        &mut self,
+        relation: Relation,
+        left: Side,
+        left_offset: i32,
+        right: Side,
+        right_offset: i32,
+    ) -> Result<Bexpr, ComposeError> {
+        // bind the anchors to the extremal positions
+        if let Side::Anchor(anchor) = left {
+            return self.exists_position(&mut |composer, position| {
+                let formula = composer.compare(
+                    relation,
+                    Side::At(position.clone()),
+                    left_offset,
+                    right.clone(),
+                    right_offset,
+                )?;
+                Ok(and(composer.extremal(anchor, &position)?, formula))
+            });
+        }
+        if let Side::Anchor(anchor) = right {
+            return self.exists_position(&mut |composer, position| {
+                let formula = composer.compare(
+                    relation,
+                    left.clone(),
+                    left_offset,
+                    Side::At(position.clone()),
+                    right_offset,
+                )?;
+                Ok(and(composer.extremal(anchor, &position)?, formula))
+            });
+        }
+        let (Side::At(x), Side::At(y)) = (left, right) else {
+            unreachable!()
+        };
+
+        // the comparison is `x + offset relation y`
+        let offset = left_offset - right_offset;
+        Ok(match relation {
+            Relation::LessEqual => self.less_equal(&x, offset, &y)?,
+            Relation::Less => self.less_equal(&x, offset + 1, &y)?,
+            Relation::GreaterEqual => self.less_equal(&y, -offset, &x)?,
+            Relation::Greater => self.less_equal(&y, 1 - offset, &x)?,
+            Relation::Equal => self.shifted(&x, offset, &y)?,
+            Relation::NotEqual => self.shifted(&x, offset, &y)?.negated(),
+        })
+    }
+
+    // `x + offset <= y`
+    fn less_equal(
+        &mut self,
+        x: &InnerPosition,
+        offset: i32,
+        y: &InnerPosition,
+    ) -> Result<Bexpr, ComposeError> {
+        if offset == 0 {
+            Ok(self.order(x, y))
+        } else if offset > 0 {
+            // the position x + offset exists and is before y
+            self.exists_position(&mut |composer, shifted| {
+                let formula = composer.shifted(x, offset, &shifted)?;
+                Ok(and(formula, composer.order(&shifted, y)))
+            })
+        } else {
+            // x - k <= y if and only if not y + k + 1 <= x
+            Ok(self.less_equal(y, 1 - offset, x)?.negated())
+        }
+    }
+
+    // `x + offset == y`, as a chain of successors
+    fn shifted(
+        &mut self,
+        x: &InnerPosition,
+        offset: i32,
+        y: &InnerPosition,
+    ) -> Result<Bexpr, ComposeError> {
+        match offset {
+            _ if offset < 0 => self.shifted(y, -offset, x),
+            0 => Ok(self.same(x, y)),
+            1 => self.successor(x, y),
+            _ => self.exists_position(&mut |composer, next| {
+                let formula = composer.successor(x, &next)?;
+                Ok(and(formula, composer.shifted(&next, offset - 1, y)?))
+            }),
+        }
+    }
+
+    // y comes right after x: no position is strictly between them
+    fn successor(&mut self, x: &InnerPosition, y: &InnerPosition) -> Result<Bexpr, ComposeError> {
+        let between = self.exists_position(&mut |composer, z| {
+            let formula = composer.less(x, &z);
+            Ok(and(formula, composer.less(&z, y)))
+        })?;
+        Ok(and(self.less(x, y), between.negated()))
+    }
+
+    // the position is the first or the last one
+    fn extremal(&mut self, anchor: Anchor, x: &InnerPosition) -> Result<Bexpr, ComposeError> {
+        let before = self.exists_position(&mut |composer, other| {
+            Ok(match anchor {
+                Anchor::First => composer.less(&other, x),
+                Anchor::Last => composer.less(x, &other),
+            })
+        })?;
+        Ok(before.negated())
+    }
+
+    fn less(&self, x: &InnerPosition, y: &InnerPosition) -> Bexpr {
+        self.order(y, x).negated()
+    }
+
+    fn order(&self, x: &InnerPosition, y: &InnerPosition) -> Bexpr {
+        self.inner
+            .order
+            .iter()
+            .find(|(l_x, l_y, _)| *l_x == x.label && *l_y == y.label)
+            .map(|(_, _, formula)| instantiate(formula, &x.vars, &y.vars))
+            .unwrap_or(Bexpr::False)
+    }
+
+    // two inner positions are equal when they have the same label and
+    // the same input positions
+    fn same(&self, x: &InnerPosition, y: &InnerPosition) -> Bexpr {
+        if x.label != y.label {
+            return Bexpr::False;
+        }
+        x.vars
+            .iter()
+            .zip(&y.vars)
+            .map(|(a, b)| {
+                Bexpr::Equal(
+                    Box::new(Bexpr::Var(a.clone())),
+                    Box::new(Bexpr::Var(b.clone())),
+                )
+            })
+            .fold(Bexpr::True, and)
+    }
+
+    fn universe(&self, x: &InnerPosition) -> Bexpr {
+        self.inner
+            .universe
+            .iter()
+            .find(|(label, _)| *label == x.label)
+            .map(|(_, formula)| instantiate(formula, &x.vars, &[]))
+            .unwrap_or(Bexpr::False)
+    }
+
+    // the inner position holds the letter
+    fn letter(&self, letter: &str, x: &InnerPosition) -> Bexpr {
+        self.inner
+            .letters
+            .iter()
+            .find(|(label, l, _)| *label == x.label && l == letter)
+            .map(|(_, _, formula)| instantiate(formula, &x.vars, &[]))
+            .unwrap_or(Bexpr::False)
+    }
+
+    // Some inner position, of any label, satisfies the formula
+    fn exists_position(
+        &mut self,
+        body: &mut dyn FnMut(&mut Self, InnerPosition) -> Result<Bexpr, ComposeError>,
+    ) -> Result<Bexpr, ComposeError> {
+        let mut formula = Bexpr::False;
+        for (label, &arity) in self.inner.arities.iter().enumerate() {
+            let vars: Vec<String> = (0..arity)
+                .map(|_| {
+                    self.fresh += 1;
+                    format!("{}{}", BOUND_PREFIX, self.fresh)
+                })
+                .collect();
+            let position = InnerPosition { label, vars };
+            let universe = self.universe(&position);
+            let vars = position.vars.clone();
+            let body = and(universe, body(self, position)?);
+            formula = or(formula, exists(&vars, body));
+        }
+        Ok(formula)
+    }
+}
+
+// Replace the variables `x1`, `x2`, ... and `y1`, `y2`, ... of a formula
+// of the inner interpretation
+fn instantiate(formula: &Bexpr, x: &[String], y: &[String]) -> Bexpr {
+    let rename = |var: &String| {
+        let vars = match var.chars().next() {
+            Some('x') => x,
+            Some('y') => y,
+            _ => return var.clone(),
+        };
+        match var[1..].parse::<usize>() {
+            Ok(index) if index >= 1 && index <= vars.len() => vars[index - 1].clone(),
+            _ => var.clone(),
+        }
+    };
+    let go = |expr: &Bexpr| Box::new(instantiate(expr, x, y));
+    match formula {
+        Bexpr::Var(var) => Bexpr::Var(rename(var)),
+        Bexpr::Label(var) => Bexpr::Label(rename(var)),
+        Bexpr::Offset(var, offset) => Bexpr::Offset(rename(var), *offset),
+        Bexpr::LetterIn(var, letters) => Bexpr::LetterIn(rename(var), letters.clone()),
+        Bexpr::Str(s) => match parse_letter_at_pos(s) {
+            Some((letter, var)) => Bexpr::Str(format!("{}({})", letter, rename(&var))),
+            None => formula.clone(),
+        },
+        Bexpr::Number(_) | Bexpr::Flag(_) | Bexpr::True | Bexpr::False => formula.clone(),
+        Bexpr::LessEqual(left, right) => Bexpr::LessEqual(go(left), go(right)),
+        Bexpr::Less(left, right) => Bexpr::Less(go(left), go(right)),
+        Bexpr::Equal(left, right) => Bexpr::Equal(go(left), go(right)),
+        Bexpr::NotEqual(left, right) => Bexpr::NotEqual(go(left), go(right)),
+        Bexpr::GreaterEqual(left, right) => Bexpr::GreaterEqual(go(left), go(right)),
+        Bexpr::Greater(left, right) => Bexpr::Greater(go(left), go(right)),
+        Bexpr::Not(inner) => Bexpr::Not(go(inner)),
+        Bexpr::And(left, right) => Bexpr::And(go(left), go(right)),
+        Bexpr::Or(left, right) => Bexpr::Or(go(left), go(right)),
+        Bexpr::Exists(var, inner) => Bexpr::Exists(var.clone(), go(inner)),
+        Bexpr::Forall(var, inner) => Bexpr::Forall(var.clone(), go(inner)),
+    }
+}
+
+// The largest index of a variable quantified by a composition
+fn last_bound(formula: &Bexpr) -> usize {
+    match formula {
+        Bexpr::Exists(var, inner) | Bexpr::Forall(var, inner) => {
+            let index = var
+                .strip_prefix(BOUND_PREFIX)
+                .and_then(|index| index.parse().ok())
+                .unwrap_or(0);
+            index.max(last_bound(inner))
+        }
+        Bexpr::LessEqual(left, right)
+        | Bexpr::Less(left, right)
+        | Bexpr::Equal(left, right)
+        | Bexpr::NotEqual(left, right)
+        | Bexpr::GreaterEqual(left, right)
+        | Bexpr::Greater(left, right)
+        | Bexpr::And(left, right)
+        | Bexpr::Or(left, right) => last_bound(left).max(last_bound(right)),
+        Bexpr::Not(inner) => last_bound(inner),
+        _ => 0,
+    }
+}
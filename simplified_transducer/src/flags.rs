@@ -0,0 +1,490 @@
+///
+/// Compilation of boolean variables. Every read of a flag in a condition
+/// is replaced by a formula over the loop variables saying which write
+/// (declaration or assignment) of the flag was executed last, so that the
+/// labelling only sees comparisons of positions and letters.
+///
+use crate::ast::{breaks_loop, remap_bexpr_with_map, Bexpr, Stmt};
+use crate::order::lexicographic_order;
+use crate::lower::HIDDEN_FLAG_PREFIX;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Where a statement is executed: its path in the program and the
+/// enclosing loops, as (loop variable, direction)
+#[derive(Debug, Clone, Default)]
+struct Point {
+    path: Vec<usize>,
+    loops: Vec<(String, usize)>,
+}
+
+/// A declaration or an assignment of a flag
+#[derive(Debug, Clone)]
+struct Write {
+    point: Point,
+    // conditions of the enclosing ifs, with the point where they are evaluated
+    guards: Vec<(Bexpr, Point)>,
+    value: Bexpr,
+}
+
+#[derive(Debug, Clone)]
+struct Flag {
+    declaration: Write,
+    assignments: Vec<Write>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FlagError {
+    /// The value of the flag depends on its own earlier values in a way
+    /// that cannot be expressed by a first order formula
+    Cyclic(String),
+    /// A boolean variable is read but never declared
+    Undeclared(String),
+}
+
+impl fmt::Display for FlagError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FlagError::Cyclic(flag) => write!(
+                f,
+                "the value of boolean variable {} depends on itself; \
+                 only variables that are always set to the same constant \
+                 may be tested before being assigned",
+                flag
+            ),
+            FlagError::Undeclared(flag) => {
+                write!(f, "boolean variable {} is read but never declared", flag)
+            }
+        }
+    }
+}
+
+/// Replace the reads of boolean variables in the conditions of `stmts`.
+/// Declarations and assignments are kept, they do not produce output.
+pub fn eliminate_flags(stmts: &[Stmt]) -> Result<Vec<Stmt>, FlagError> {
+    let mut flags = HashMap::new();
+    collect(stmts, &Point::default(), &[], &mut flags);
+
+    let mut eliminator = Eliminator {
+        flags: &flags,
+        fresh: 0,
+        assumed: vec![],
+    };
+    eliminator.rewrite(stmts, &Point::default())
+}
+
+/// Replace the `break` statements by a flag for each loop they exit,
+/// set by the break and guarding the rest of the loop: the statements
+/// of an iteration only run when no earlier iteration reached a break.
+pub fn eliminate_breaks(stmts: &[Stmt]) -> Vec<Stmt> {
+    remove_breaks(stmts, None, &mut 0)
+}
+
+// `flag` is the flag of the innermost loop, `breaks` the number of
+// flags introduced so far
+fn remove_breaks(stmts: &[Stmt], flag: Option<&str>, breaks: &mut usize) -> Vec<Stmt> {
+    let mut removed = vec![];
+    for (index, stmt) in stmts.iter().enumerate() {
+        match stmt {
+            Stmt::Break => {
+                let flag = flag.expect("the parser rejects breaks outside of loops");
+                removed.push(Stmt::Assign(flag.to_string(), Bexpr::True));
+                // the rest of the block is never run
+                return removed;
+            }
+            Stmt::For0(var, body) | Stmt::For1(var, body) => {
+                let body = if breaks_loop(body) {
+                    *breaks += 1;
+                    let broken = format!("{}break{}", HIDDEN_FLAG_PREFIX, breaks);
+                    removed.push(Stmt::Declare(broken.clone(), Bexpr::False));
+                    let body = remove_breaks(body, Some(&broken), breaks);
+                    vec![Stmt::If(Bexpr::Flag(broken).negated(), body)]
+                } else {
+                    remove_breaks(body, None, breaks)
+                };
+                removed.push(match stmt {
+                    Stmt::For0(_, _) => Stmt::For0(var.clone(), body),
+                    _ => Stmt::For1(var.clone(), body),
+                });
+            }
+            Stmt::If(condition, body) => {
+                removed.push(Stmt::If(condition.clone(), remove_breaks(body, flag, breaks)));
+                // the rest of the block only runs if the branch did not break
+                if let (Some(flag), true) = (flag, breaks_loop(body)) {
+                    let rest = remove_breaks(&stmts[index + 1..], Some(flag), breaks);
+                    if !rest.is_empty() {
+                        removed.push(Stmt::If(Bexpr::Flag(flag.to_string()).negated(), rest));
+                    }
+                    return removed;
+                }
+            }
+            _ => removed.push(stmt.clone()),
+        }
+    }
+    removed
+}
+
+fn collect(
+    stmts: &[Stmt],
+    point: &Point,
+    guards: &[(Bexpr, Point)],
+    flags: &mut HashMap<String, Flag>,
+) {
+    for (index, stmt) in stmts.iter().enumerate() {
+        let mut here = point.clone();
+        here.path.push(index);
+        match stmt {
+            Stmt::Print(_, _) | Stmt::Break => {}
+            Stmt::ForConst(_, _, _) | Stmt::Repeat(_, _) => {
+                unreachable!("constant loops are unrolled before the flags are eliminated")
+            }
+            Stmt::For0(var, body) | Stmt::For1(var, body) => {
+                let direction = if matches!(stmt, Stmt::For0(_, _)) {
+                    0
+                } else {
+                    1
+                };
+                here.loops.push((var.clone(), direction));
+                collect(body, &here, guards, flags);
+            }
+            Stmt::If(condition, body) => {
+                let mut guards = guards.to_vec();
+                guards.push((condition.clone(), here.clone()));
+                collect(body, &here, &guards, flags);
+            }
+            Stmt::Declare(flag, value) => {
+                let declaration = Write {
+                    point: here,
+                    guards: guards.to_vec(),
+                    value: value.clone(),
+                };
+                flags.insert(
+                    flag.clone(),
+                    Flag {
+                        declaration,
+                        assignments: vec![],
+                    },
+                );
+            }
+            Stmt::Assign(flag, value) => {
+                let assignment = Write {
+                    point: here,
+                    guards: guards.to_vec(),
+                    value: value.clone(),
+                };
+                // the parser rejects assignments to undeclared flags
+                if let Some(flag) = flags.get_mut(flag) {
+                    flag.assignments.push(assignment);
+                }
+            }
+        }
+    }
+}
+
+struct Eliminator<'a> {
+    flags: &'a HashMap<String, Flag>,
+    fresh: usize,
+    // flags whose value is known, whatever the point they are read at
+    assumed: Vec<(String, Bexpr)>,
+}
+
+/// A write executed at some iteration of its loops: the loop variables
+/// that are quantified are renamed to fresh variables
+struct Execution<'a> {
+    write: &'a Write,
+    renaming: HashMap<String, String>,
+    bound: Vec<String>,
+}
+
+impl<'a> Eliminator<'a> {
+    fn rewrite(&mut self, stmts: &[Stmt], point: &Point) -> Result<Vec<Stmt>, FlagError> {
+        let mut rewritten = vec![];
+        for (index, stmt) in stmts.iter().enumerate() {
+            let mut here = point.clone();
+            here.path.push(index);
+            rewritten.push(match stmt {
+                Stmt::For0(var, body) => {
+                    here.loops.push((var.clone(), 0));
+                    Stmt::For0(var.clone(), self.rewrite(body, &here)?)
+                }
+                Stmt::For1(var, body) => {
+                    here.loops.push((var.clone(), 1));
+                    Stmt::For1(var.clone(), self.rewrite(body, &here)?)
+                }
+                Stmt::If(condition, body) => {
+                    let condition = self.resolve(condition, &here, &mut vec![])?;
+                    Stmt::If(condition, self.rewrite(body, &here)?)
+                }
+                _ => stmt.clone(),
+            });
+        }
+        Ok(rewritten)
+    }
+
+    // Replace every flag of `expr`, evaluated at `point`. `stack` holds the
+    // flags whose value is being computed, with the point where they are read
+    // and the number of assumptions made at that time.
+    fn resolve(
+        &mut self,
+        expr: &Bexpr,
+        point: &Point,
+        stack: &mut Vec<(String, Vec<usize>, usize)>,
+    ) -> Result<Bexpr, FlagError> {
+        match expr {
+            Bexpr::Flag(flag) => self.value_at(flag, point, stack),
+            Bexpr::And(left, right) => Ok(Bexpr::And(
+                Box::new(self.resolve(left, point, stack)?),
+                Box::new(self.resolve(right, point, stack)?),
+            )),
+            Bexpr::Or(left, right) => Ok(Bexpr::Or(
+                Box::new(self.resolve(left, point, stack)?),
+                Box::new(self.resolve(right, point, stack)?),
+            )),
+            Bexpr::Not(inner) => Ok(Bexpr::Not(Box::new(self.resolve(inner, point, stack)?))),
+            Bexpr::Exists(var, inner) => Ok(Bexpr::Exists(
+                var.clone(),
+                Box::new(self.resolve(inner, point, stack)?),
+            )),
+            Bexpr::Forall(var, inner) => Ok(Bexpr::Forall(
+                var.clone(),
+                Box::new(self.resolve(inner, point, stack)?),
+            )),
+            // comparisons of positions and letters do not contain flags
+            _ => Ok(expr.clone()),
+        }
+    }
+
+    // The value of `flag` when the statement at `point` is executed
+    fn value_at(
+        &mut self,
+        flag: &str,
+        point: &Point,
+        stack: &mut Vec<(String, Vec<usize>, usize)>,
+    ) -> Result<Bexpr, FlagError> {
+        if let Some((_, value)) = self.assumed.iter().rev().find(|(f, _)| f == flag) {
+            return Ok(value.clone());
+        }
+        // the value at a point may depend on the value of the same flag at
+        // another point, but reaching the same point again (under the same
+        // assumptions) would never end
+        let assumptions = self.assumed.len();
+        if stack
+            .iter()
+            .any(|(f, path, assumed)| f == flag && *path == point.path && *assumed == assumptions)
+        {
+            // name a flag of the program rather than one made for an else
+            let cycle = stack.iter().skip_while(|(f, _, _)| f != flag);
+            let named = cycle
+                .map(|(f, _, _)| f)
+                .find(|f| !f.starts_with(HIDDEN_FLAG_PREFIX))
+                .map_or(flag, |f| f.as_str());
+            return Err(FlagError::Cyclic(named.to_string()));
+        }
+        let flags = self.flags;
+        let info = match flags.get(flag) {
+            Some(info) => info,
+            None => return Err(FlagError::Undeclared(flag.to_string())),
+        };
+        stack.push((flag.to_string(), point.path.clone(), assumptions));
+
+        // the flag is declared in a block around `point`, the value of the
+        // declaration only depends on the loops shared with `point`
+        let declaration = &info.declaration;
+        let initial = self.resolve(&declaration.value, &declaration.point, stack)?;
+        let no_renaming = HashMap::new();
+
+        let value = match constant_value(&info.assignments) {
+            Some(constant) => {
+                // The flag only changes once: it holds `constant` as soon as
+                // one assignment was executed. Every read before the first
+                // assignment sees the initial value, so the guards may use
+                // the initial value instead of the flag itself.
+                self.assumed.push((flag.to_string(), initial.clone()));
+                let mut any = Bexpr::False;
+                for write in &info.assignments {
+                    let execution = self.execution(write, declaration);
+                    let before = before(execution.at(), (point, &no_renaming));
+                    if is_false(&before) {
+                        continue;
+                    }
+                    let executed = self.executed(&execution, stack)?;
+                    any = or(any, exists(&execution.bound, and(before, executed)));
+                }
+                self.assumed.pop();
+                if constant {
+                    or(any, initial)
+                } else {
+                    and(any.negated(), initial)
+                }
+            }
+            None => {
+                // The value of the last assignment executed before `point`,
+                // or the initial value when there is none
+                let mut value = Bexpr::False;
+                let mut some_assignment = Bexpr::False;
+                for write in &info.assignments {
+                    let execution = self.execution(write, declaration);
+                    let before_point = before(execution.at(), (point, &no_renaming));
+                    if is_false(&before_point) {
+                        continue;
+                    }
+                    let executed = self.executed(&execution, stack)?;
+                    let written = self.resolve(&write.value, &write.point, stack)?;
+                    let written = remap_bexpr_with_map(&written, &execution.renaming);
+
+                    // no assignment is executed between this one and `point`
+                    let mut overwritten = Bexpr::False;
+                    for later in &info.assignments {
+                        let later_execution = self.execution(later, declaration);
+                        let between = and(
+                            before(execution.at(), later_execution.at()),
+                            before(later_execution.at(), (point, &no_renaming)),
+                        );
+                        if is_false(&between) {
+                            continue;
+                        }
+                        let later_executed = self.executed(&later_execution, stack)?;
+                        overwritten = or(
+                            overwritten,
+                            exists(&later_execution.bound, and(between, later_executed)),
+                        );
+                    }
+
+                    let last = and(
+                        and(before_point.clone(), executed.clone()),
+                        and(written, overwritten.negated()),
+                    );
+                    value = or(value, exists(&execution.bound, last));
+                    some_assignment = or(
+                        some_assignment,
+                        exists(&execution.bound, and(before_point, executed)),
+                    );
+                }
+                or(value, and(some_assignment.negated(), initial))
+            }
+        };
+
+        stack.pop();
+        Ok(value)
+    }
+
+    // Rename the loop variables of `write` that are not shared with the
+    // declaration: they are quantified in the formulas
+    fn execution<'w>(&mut self, write: &'w Write, declaration: &Write) -> Execution<'w> {
+        let mut renaming = HashMap::new();
+        let mut bound = vec![];
+        for (var, _) in write.point.loops.iter().skip(declaration.point.loops.len()) {
+            self.fresh += 1;
+            let fresh = format!("Q{}", self.fresh);
+            renaming.insert(var.clone(), fresh.clone());
+            bound.push(fresh);
+        }
+        Execution {
+            write,
+            renaming,
+            bound,
+        }
+    }
+
+    // The condition for the write to be executed: the guards around it hold
+    fn executed(
+        &mut self,
+        execution: &Execution,
+        stack: &mut Vec<(String, Vec<usize>, usize)>,
+    ) -> Result<Bexpr, FlagError> {
+        let mut executed = Bexpr::True;
+        for (guard, guard_point) in &execution.write.guards {
+            let guard = self.resolve(guard, guard_point, stack)?;
+            executed = and(executed, remap_bexpr_with_map(&guard, &execution.renaming));
+        }
+        Ok(executed)
+    }
+}
+
+impl Execution<'_> {
+    fn at(&self) -> (&Point, &HashMap<String, String>) {
+        (&self.write.point, &self.renaming)
+    }
+}
+
+// The formula saying that `first` is executed strictly before `second`,
+// both points being given with the renaming of their loop variables
+fn before(
+    first: (&Point, &HashMap<String, String>),
+    second: (&Point, &HashMap<String, String>),
+) -> Bexpr {
+    let name = |var: &String, renaming: &HashMap<String, String>| {
+        renaming.get(var).cloned().unwrap_or_else(|| var.clone())
+    };
+    let (first_point, first_renaming) = first;
+    let (second_point, second_renaming) = second;
+    let shared: Vec<(String, String, usize)> = first_point
+        .loops
+        .iter()
+        .zip(second_point.loops.iter())
+        .take_while(|(a, b)| a.0 == b.0)
+        .map(|((var, direction), _)| {
+            (
+                name(var, first_renaming),
+                name(var, second_renaming),
+                *direction,
+            )
+        })
+        // loops around the declaration are at the same iteration
+        .filter(|(first_var, second_var, _)| first_var != second_var)
+        .collect();
+    lexicographic_order(&shared, first_point.path < second_point.path)
+}
+
+// The value all the assignments set, if they all set the same constant
+fn constant_value(assignments: &[Write]) -> Option<bool> {
+    let constant = |write: &Write| match &write.value {
+        Bexpr::True => Some(true),
+        Bexpr::False => Some(false),
+        _ => None,
+    };
+    let first = constant(assignments.first()?)?;
+    assignments
+        .iter()
+        .all(|write| constant(write) == Some(first))
+        .then_some(first)
+}
+
+fn is_true(expr: &Bexpr) -> bool {
+    matches!(expr, Bexpr::True)
+}
+
+fn is_false(expr: &Bexpr) -> bool {
+    matches!(expr, Bexpr::False)
+}
+
+// Conjunction and disjunction, simplifying the constants away
+pub(crate) fn and(left: Bexpr, right: Bexpr) -> Bexpr {
+    if is_true(&left) || is_false(&right) {
+        right
+    } else if is_true(&right) || is_false(&left) {
+        left
+    } else {
+        Bexpr::And(Box::new(left), Box::new(right))
+    }
+}
+
+pub(crate) fn or(left: Bexpr, right: Bexpr) -> Bexpr {
+    if is_false(&left) || is_true(&right) {
+        right
+    } else if is_false(&right) || is_true(&left) {
+        left
+    } else {
+        Bexpr::Or(Box::new(left), Box::new(right))
+    }
+}
+
+pub(crate) fn exists(vars: &[String], body: Bexpr) -> Bexpr {
+    // an existential over a true body still says that the word is not empty
+    if is_false(&body) {
+        return body;
+    }
+    vars.iter()
+        .rev()
+        .fold(body, |body, var| Bexpr::Exists(var.clone(), Box::new(body)))
+}
@@ -0,0 +1,423 @@
+///
+/// Printing of programs back to the brace syntax. The program is printed
+/// from its surface syntax tree, so the file keeps its names, letter
+/// classes, maps, procedures, transducers and loops as they are written;
+/// only the layout changes. A blank line between two statements is kept.
+///
+use crate::ast::{Alphabet, InputMode};
+use crate::diagnostic::Span;
+use crate::lexer::LENGTH_VARIABLE;
+use crate::surface::{
+    Expr, ExprKind, Header, Letter, LetterSet, Name, Printed, Range, Section, Source, Statement,
+    StatementKind,
+};
+
+const INDENT: &str = "    ";
+
+/// Print a parsed file in the brace syntax
+pub fn format_source(source: &Source) -> String {
+    let mut printer = Printer {
+        alphabet: source
+            .header
+            .as_ref()
+            .map_or_else(Alphabet::default, Header::alphabet),
+        lines: Vec::new(),
+    };
+    if let Some(header) = &source.header {
+        printer.line(0, self::header(header));
+    }
+    let previous = source.header.as_ref().map(|header| header.span);
+    printer.block(&source.statements, previous, 0);
+    printer.finish()
+}
+
+struct Printer {
+    // the letters of the enclosing program, to join the printed strings
+    // only when they are parsed back into the same letters
+    alphabet: Alphabet,
+    lines: Vec<String>,
+}
+
+impl Printer {
+    fn finish(self) -> String {
+        let mut source = self.lines.join("\n");
+        source.push('\n');
+        source
+    }
+
+    fn line(&mut self, depth: usize, text: String) {
+        self.lines.push(format!("{}{}", INDENT.repeat(depth), text));
+    }
+
+    // The statements of a block, separated by a blank line where the
+    // source has blank lines between them. `previous` is what comes
+    // before the first statement at the same depth, like the header.
+    fn block(&mut self, statements: &[Statement], mut previous: Option<Span>, depth: usize) {
+        for statement in statements {
+            if previous.is_some_and(|span| statement.span.start.line > span.end.line + 1) {
+                self.lines.push(String::new());
+            }
+            self.statement(statement, depth);
+            previous = Some(statement.span);
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement, depth: usize) {
+        match &statement.kind {
+            StatementKind::Print(printed) => {
+                let printed = self.concatenation(printed);
+                self.line(depth, format!("print({})", printed));
+            }
+            StatementKind::For(var, range, body) => {
+                let header = format!("for {} in {}", var.name, self::range(range));
+                self.braces(header, body, depth);
+            }
+            StatementKind::Repeat(count, _, body) => {
+                self.braces(format!("repeat {}", count), body, depth)
+            }
+            StatementKind::If(branches, otherwise) => {
+                for (index, (condition, body)) in branches.iter().enumerate() {
+                    let keyword = if index == 0 {
+                        "if".to_string()
+                    } else {
+                        format!("{} else if", self.closing())
+                    };
+                    self.braces(format!("{} {}", keyword, expr(condition)), body, depth);
+                }
+                if let Some(body) = otherwise {
+                    let keyword = format!("{} else", self.closing());
+                    self.braces(keyword, body, depth);
+                }
+            }
+            StatementKind::Declare(flag, value) => {
+                self.line(depth, format!("var {} := {}", flag.name, expr(value)))
+            }
+            // the declarations of the paper syntax
+            StatementKind::Initialize(flag) => {
+                self.line(depth, format!("var {} := False", flag.name))
+            }
+            StatementKind::Assign(flag, value) => {
+                self.line(depth, format!("{} := {}", flag.name, expr(value)))
+            }
+            StatementKind::Break => self.line(depth, "break".to_string()),
+            StatementKind::Skip => {}
+            StatementKind::Proc(name, parameters, body) => {
+                let header = format!("proc {}({})", name.name, names(parameters, ", "));
+                self.braces(header, body, depth);
+            }
+            StatementKind::Call(name, arguments) => {
+                self.line(depth, format!("{}({})", name.name, names(arguments, ", ")))
+            }
+            StatementKind::Class(class, letters) => {
+                let letters = letter_list(letters);
+                self.line(depth, format!("class {} = {}", class.name, letters));
+            }
+            StatementKind::Map(map, table) => {
+                let entries: Vec<String> = table
+                    .iter()
+                    .map(|(from, to)| format!("{} -> {}", letter(from), letter(to)))
+                    .collect();
+                let entries = entries.join(", ");
+                self.line(depth, format!("map {} = {{{}}}", map.name, entries));
+            }
+            StatementKind::Transducer(name, header, body) => {
+                let alphabet = header
+                    .as_ref()
+                    .map_or_else(|| self.alphabet.clone(), Header::alphabet);
+                let file_alphabet = std::mem::replace(&mut self.alphabet, alphabet);
+                self.line(depth, format!("transducer {} {{", name.name));
+                if let Some(header) = header {
+                    self.line(depth + 1, self::header(header));
+                }
+                let previous = header.as_ref().map(|header| header.span);
+                self.block(body, previous, depth + 1);
+                self.line(depth, "}".to_string());
+                self.alphabet = file_alphabet;
+            }
+            StatementKind::Main(stages) => {
+                self.line(depth, format!("main = {}", names(stages, " . ")))
+            }
+        }
+    }
+
+    // A block in braces after a header, written `{}` when it is empty
+    fn braces(&mut self, header: String, body: &[Statement], depth: usize) {
+        if body.is_empty() {
+            self.line(depth, format!("{} {{}}", header));
+            return;
+        }
+        self.line(depth, format!("{} {{", header));
+        self.block(body, None, depth + 1);
+        self.line(depth, "}".to_string());
+    }
+
+    // The closing brace of the branch just printed, taken back to be
+    // followed by `else`
+    fn closing(&mut self) -> String {
+        let line = self.lines.pop().unwrap_or_default();
+        line.trim_start().to_string()
+    }
+
+    // The printed terms `e1 + e2 + ...`, where consecutive strings are
+    // joined when they are parsed back into the same letters
+    fn concatenation(&self, printed: &[Printed]) -> String {
+        let mut terms: Vec<String> = Vec::new();
+        let mut string: Option<String> = None;
+        for term in printed {
+            if let Printed::Str(s) = term {
+                string = match string {
+                    Some(joined) if self.joins(&joined, &s.letter) => Some(joined + &s.letter),
+                    Some(joined) => {
+                        terms.push(format!("\"{}\"", joined));
+                        Some(s.letter.clone())
+                    }
+                    None => Some(s.letter.clone()),
+                };
+                continue;
+            }
+            terms.extend(string.take().map(|joined| format!("\"{}\"", joined)));
+            terms.push(match term {
+                Printed::Label(var) => format!("{}.label", var.name),
+                Printed::Map(map, var) => format!("{}[{}.label]", map.name, var.name),
+                Printed::Str(_) => unreachable!(),
+            });
+        }
+        terms.extend(string.map(|joined| format!("\"{}\"", joined)));
+        terms.join(" + ")
+    }
+
+    // Whether two printed strings are the same letters once joined
+    fn joins(&self, left: &str, right: &str) -> bool {
+        let mut letters = self.letters(left);
+        letters.extend(self.letters(right));
+        self.letters(&format!("{}{}", left, right)) == letters
+    }
+
+    // The letters printed by a string, as the lowering reads them
+    fn letters(&self, s: &str) -> Vec<String> {
+        if self.alphabet.output.iter().any(|letter| letter == s) {
+            vec![s.to_string()]
+        } else {
+            self.alphabet.mode.split(s)
+        }
+    }
+}
+
+// The `alphabet { ... }` header, its sections in the order the parser
+// expects them
+fn header(header: &Header) -> String {
+    let mut sections = Vec::new();
+    if let Some(mode) = header.mode.filter(|mode| *mode != InputMode::default()) {
+        sections.push(format!("mode {}", mode.name()));
+    }
+    if let Some(input) = &header.input {
+        sections.push(format!("input {}", section(input)));
+    }
+    if let Some(output) = &header.output {
+        sections.push(format!("output {}", section(output)));
+    }
+    format!("alphabet {{ {} }}", sections.join(" "))
+}
+
+fn section(section: &Section) -> String {
+    match section {
+        Section::Symbols(s) => format!("\"{}\"", s),
+        Section::List(letters) => {
+            let letters: Vec<String> = letters.iter().map(|l| format!("\"{}\"", l)).collect();
+            format!("{{{}}}", letters.join(", "))
+        }
+    }
+}
+
+fn range(range: &Range) -> String {
+    let reversed = |reversed: bool, range: String| {
+        if reversed {
+            format!("rev({})", range)
+        } else {
+            range
+        }
+    };
+    match range {
+        Range::Positions {
+            start,
+            end,
+            inclusive,
+        } => {
+            let dots = if *inclusive { "..=" } else { ".." };
+            format!("{}{}{}", expr(start), dots, expr(end))
+        }
+        Range::Blocks {
+            separator,
+            reversed: r,
+        } => reversed(*r, format!("blocks(stdin, {})", letter(separator))),
+        Range::Block { block, reversed: r } => reversed(*r, block.name.clone()),
+        // the loops over the input of the paper syntax
+        Range::Stdin { reversed: false } => format!("0..{}", LENGTH_VARIABLE),
+        Range::Stdin { reversed: true } => format!("{}..0", LENGTH_VARIABLE),
+    }
+}
+
+// A condition or a term. The parentheses of the source are kept, and
+// the tree has no others to add: it is parsed from the text it prints.
+fn expr(expr: &Expr) -> String {
+    match &expr.kind {
+        ExprKind::True => "True".to_string(),
+        ExprKind::False => "False".to_string(),
+        ExprKind::Number(k) => k.to_string(),
+        ExprKind::Str(s) => format!("\"{}\"", s),
+        ExprKind::Var(var) => var.name.clone(),
+        ExprKind::Offset(var, offset) if *offset < 0 => format!("{} - {}", var.name, -offset),
+        ExprKind::Offset(var, offset) => format!("{} + {}", var.name, offset),
+        ExprKind::Label(var) => format!("{}.label", var.name),
+        ExprKind::First(var) => format!("first({})", var.name),
+        ExprKind::Last(var) => format!("last({})", var.name),
+        ExprKind::In(var, LetterSet::Class(class)) => {
+            format!("{}.label in {}", var.name, class.name)
+        }
+        ExprKind::In(var, LetterSet::Letters(letters)) => {
+            format!("{}.label in {}", var.name, letter_list(letters))
+        }
+        ExprKind::Compare(left, comparison, _, right) => {
+            format!("{} {} {}", self::expr(left), comparison, self::expr(right))
+        }
+        ExprKind::Not(inner) => format!("!{}", self::expr(inner)),
+        ExprKind::And(left, right) => format!("{} && {}", self::expr(left), self::expr(right)),
+        ExprKind::Or(left, right) => format!("{} || {}", self::expr(left), self::expr(right)),
+        ExprKind::Exists(var, inner) => format!("exists {}. {}", var.name, self::expr(inner)),
+        ExprKind::Forall(var, inner) => format!("forall {}. {}", var.name, self::expr(inner)),
+        ExprKind::Paren(inner) => format!("({})", self::expr(inner)),
+    }
+}
+
+// A letter, quoted unless it is written as an identifier in a letter map
+fn letter(letter: &Letter) -> String {
+    if letter.quoted {
+        format!("\"{}\"", letter.letter)
+    } else {
+        letter.letter.clone()
+    }
+}
+
+fn letter_list(letters: &[Letter]) -> String {
+    let letters: Vec<String> = letters.iter().map(letter).collect();
+    format!("{{{}}}", letters.join(", "))
+}
+
+fn names(names: &[Name], separator: &str) -> String {
+    let names: Vec<&str> = names.iter().map(|name| name.name.as_str()).collect();
+    names.join(separator)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+    use crate::lexer::{tokenize, Syntax};
+    use crate::Parser;
+    use std::fs;
+
+    fn format(source: &str) -> String {
+        let tokens = tokenize(source).unwrap();
+        format_source(&Parser::new(tokens).parse_source(Syntax::Braces).unwrap())
+    }
+
+    // The output of a program on some words
+    fn outputs(source: &str, words: &[&str]) -> Vec<String> {
+        let program = Parser::new(tokenize(source).unwrap()).parse().unwrap();
+        (words.iter())
+            .map(|word| Interpreter::new(word).run(&program.stmts))
+            .collect()
+    }
+
+    #[test]
+    fn test_format() {
+        let formatted = format(
+            r##"for  i in n..0 { if !(i<2)&&i.label!="a" { print("a"+"b"+i.label)
+            var seen_i := i.label in {"a", "#"} } }"##,
+        );
+        assert_eq!(
+            formatted,
+            r##"for i in n..0 {
+    if !(i < 2) && i.label != "a" {
+        print("ab" + i.label)
+        var seen_i := i.label in {"a", "#"}
+    }
+}
+"##
+        );
+
+        // the constructions that the lowering expands are printed as
+        // they are written
+        let source = r##"alphabet { input "ab#" output "ab#" }
+
+class marks = {"#"}
+map swap = {a -> b, "b" -> "a"}
+proc copy(i) {
+    print(swap[i.label])
+}
+
+for i in 0..n {
+    for j in i..=2 {
+        if j.label in marks {
+            break
+        }
+        copy(j)
+    }
+    repeat 2 {
+        print("#")
+    }
+    if exists k. k < i && k.label == "#" || first(i) {
+        print("#")
+    } else if (i.label == "a" || last(i)) && i != n - 1 {} else {
+        print(i.label)
+    }
+}
+for b in rev(blocks(stdin, "#")) {
+    for i in rev(b) {
+        print(i.label)
+    }
+}
+"##;
+        let formatted = format(source);
+        assert_eq!(formatted, source);
+        let words = ["", "a", "ab#b", "#ba#a"];
+        assert_eq!(outputs(&formatted, &words), outputs(source, &words));
+
+        let source = "transducer double {
+    print(\"a\")
+}
+transducer swap {
+    alphabet { input \"ab\" output \"ba\" }
+    for i in 0..n {}
+}
+main = swap . double
+";
+        assert_eq!(format(source), source);
+    }
+
+    #[test]
+    fn test_format_input_modes() {
+        // the strings are written with the symbols of the mode, and
+        // joined when they are read back as the same letters
+        let source = "alphabet { mode bytes input \"aé\" output {\"a\", \"\\xc3\", \"\\xa9\"} }
+            for i in 0..n { print(\"é\" + \"\\xc3\" + i.label + \"a\" + \"a\") }";
+        let formatted = format(source);
+        assert_eq!(
+            formatted,
+            "alphabet { mode bytes input \"aé\" output {\"a\", \"\\xc3\", \"\\xa9\"} }
+for i in 0..n {
+    print(\"é\" + \"\\xc3\" + i.label + \"aa\")
+}
+"
+        );
+        assert_eq!(format(&formatted), formatted);
+    }
+
+    #[test]
+    fn test_format_examples() {
+        for path in ["sample.txt", "ex.txt", "examples/cyclic.txt"] {
+            let source = fs::read_to_string(path).unwrap();
+            assert_eq!(format(&source), source, "{} is not formatted", path);
+        }
+    }
+}
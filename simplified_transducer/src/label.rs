@@ -1,5 +1,6 @@
-use crate::ast::{Bexpr, Pexpr, Stmt};
+use crate::ast::{Alphabet, Bexpr, Pexpr, Stmt};
 
+#[allow(clippy::too_many_arguments)]
 pub fn traverse_and_label(
     stmts: &[Stmt],
     path: &mut Vec<usize>,
@@ -7,12 +8,13 @@ pub fn traverse_and_label(
     current_if: Option<Bexpr>,
     universe_formulas: &mut Vec<(Vec<String>, Bexpr)>,
     for_vars: &mut Vec<String>,
-    for0_or_for1: &mut Vec<usize>,
-    label_formulas: &mut Vec<(String, String, String)>,
+    for0_or_for1: &mut Vec<(String, usize)>,
+    label_formulas: &mut Vec<Vec<(String, Bexpr)>>,
+    alphabet: &Alphabet,
 ) {
     for (index, stmt) in stmts.iter().enumerate() {
         match stmt {
-            Stmt::Print(expr) => {
+            Stmt::Print(expr, _) => {
                 let mut current_path = path.clone();
                 current_path.push(index);
                 labels.push(current_path.clone());
@@ -20,17 +22,22 @@ pub fn traverse_and_label(
                 let universe_formula = if let Some(ref if_expr) = current_if {
                     if_expr.clone()
                 } else {
-                    Bexpr::Var("T".to_string())
+                    Bexpr::True
                 };
                 universe_formulas.push((for_vars.clone(), universe_formula));
 
-                let label_formula_a = generate_label_formula(expr, 'a');
-                let label_formula_b = generate_label_formula(expr, 'b');
-                let label_formula_hash = generate_label_formula(expr, '#');
-                label_formulas.push((label_formula_a, label_formula_b, label_formula_hash));
+                // one formula for every output letter
+                label_formulas.push(
+                    (alphabet.output.iter())
+                        .map(|letter| {
+                            let formula = generate_label_formula(expr, letter, &alphabet.input);
+                            (letter.clone(), formula)
+                        })
+                        .collect(),
+                );
             }
             Stmt::For0(var, inner_stmts) => {
-                for0_or_for1.push(0);
+                for0_or_for1.push((var.clone(), 0));
                 path.push(index);
                 for_vars.push(var.clone());
                 traverse_and_label(
@@ -42,13 +49,14 @@ pub fn traverse_and_label(
                     for_vars,
                     for0_or_for1,
                     label_formulas,
+                    alphabet,
                 );
                 for_vars.pop();
                 path.pop();
             }
 
             Stmt::For1(var, inner_stmts) => {
-                for0_or_for1.push(1);
+                for0_or_for1.push((var.clone(), 1));
                 path.push(index);
                 for_vars.push(var.clone());
                 traverse_and_label(
@@ -60,6 +68,7 @@ pub fn traverse_and_label(
                     for_vars,
                     for0_or_for1,
                     label_formulas,
+                    alphabet,
                 );
                 for_vars.pop();
                 path.pop();
@@ -81,22 +90,44 @@ pub fn traverse_and_label(
                     for_vars,
                     for0_or_for1,
                     label_formulas,
+                    alphabet,
                 );
                 path.pop();
             }
+
+            // boolean variables produce no output, their reads have
+            // already been replaced by formulas (see `flags`)
+            Stmt::Declare(_, _) | Stmt::Assign(_, _) => {}
+            Stmt::Break => unreachable!("breaks are compiled away before the labelling"),
+            Stmt::ForConst(_, _, _) | Stmt::Repeat(_, _) => {
+                unreachable!("constant loops are unrolled before the labelling")
+            }
         }
     }
 }
 
-fn generate_label_formula(expr: &Pexpr, ch: char) -> String {
+fn generate_label_formula(expr: &Pexpr, letter: &str, input: &[String]) -> Bexpr {
     match expr {
-        Pexpr::Label(label) => format!("{}({})", ch, label),
+        Pexpr::Label(label) if input.iter().any(|l| l == letter) => {
+            Bexpr::Str(format!("{}({})", letter, label))
+        }
+        Pexpr::Label(_) => Bexpr::False,
         Pexpr::Str(s) => {
-            if s.contains(ch) {
-                "T".to_string()
+            if s == letter {
+                Bexpr::True
             } else {
-                "F".to_string()
+                Bexpr::False
             }
         }
+        // the input letters sent to `letter`, unmapped letters being unchanged
+        Pexpr::Map(table, label) => input
+            .iter()
+            .filter(|l| match table.iter().find(|(from, _)| from == *l) {
+                Some((_, to)) => to == letter,
+                None => *l == letter,
+            })
+            .map(|l| Bexpr::Str(format!("{}({})", l, label)))
+            .reduce(|left, right| Bexpr::Or(Box::new(left), Box::new(right)))
+            .unwrap_or(Bexpr::False),
     }
 }
@@ -1,13 +1,16 @@
-pub mod ast;
 pub mod bexpr_evaluator;
-pub mod interpreter;
+pub mod compile;
+pub mod composition;
+pub mod flags;
+pub mod format;
 pub mod label;
-pub mod lexer;
+pub mod lsp;
 pub mod order;
-pub mod parser;
 pub mod qf_interpretation;
 pub mod qf_pullback;
 pub mod two_sorted_formulas;
-pub use ast::{Bexpr, Pexpr, Stmt};
+pub use transducer_front_end::{ast, check, diagnostic, interpreter, lexer, lower, parser, surface};
+pub use ast::{Alphabet, Bexpr, Pexpr, Program, Stmt};
+pub use diagnostic::Diagnostic;
 pub use lexer::tokenize;
 pub use parser::Parser;
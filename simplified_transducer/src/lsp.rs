@@ -0,0 +1,386 @@
+///
+/// A language server for the transducer programs, speaking the Language
+/// Server Protocol over stdio (see the `transducer-lsp` binary). It
+/// publishes the diagnostics of the lexer, the parser and the checker,
+/// shows on hover the label paths and the arities of the outputs of a
+/// `print`, goes to the definition of the loop variables, and formats
+/// the programs written in the brace syntax.
+///
+use crate::ast::{Alphabet, Program, Stmt};
+use crate::check::check;
+use crate::compile::{unroll, CompileError};
+use crate::diagnostic::{Diagnostic, Location, Span};
+use crate::flags::{eliminate_breaks, eliminate_flags};
+use crate::format::format_source;
+use crate::label::traverse_and_label;
+use crate::lexer::{tokenize_with, SpannedToken, Syntax, Token};
+use crate::parser::Parser;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+/// Answer the messages read from `input` on `output`, until the client
+/// sends `exit`
+pub fn serve(mut input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+    let mut server = Server::new();
+    while let Some(message) = read_message(&mut input)? {
+        for reply in server.handle(&message) {
+            write_message(&mut output, &reply)?;
+        }
+        if message["method"] == "exit" {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Read a message framed by a `Content-Length` header, or `None` at the
+/// end of the input
+pub fn read_message(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let length = length.ok_or_else(|| invalid_data("missing `Content-Length` header"))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|error| invalid_data(&error.to_string()))
+}
+
+/// Write a message framed by a `Content-Length` header
+pub fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// The state of the server: the text of the open documents
+#[derive(Default)]
+pub struct Server {
+    documents: HashMap<String, String>,
+}
+
+impl Server {
+    pub fn new() -> Self {
+        Server::default()
+    }
+
+    /// Handle a request or a notification, returning the messages to
+    /// send back to the client
+    pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        // notifications have no id and get no response
+        let id = match message.get("id") {
+            Some(id) => id.clone(),
+            None => {
+                return match method {
+                    "textDocument/didOpen" => {
+                        let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                        self.documents.insert(uri.clone(), text.to_string());
+                        vec![self.diagnostics(&uri)]
+                    }
+                    "textDocument/didChange" => {
+                        // the whole text is sent on every change
+                        let changes = params["contentChanges"].as_array();
+                        if let Some(text) = changes
+                            .and_then(|changes| changes.last())
+                            .and_then(|change| change["text"].as_str())
+                        {
+                            self.documents.insert(uri.clone(), text.to_string());
+                        }
+                        vec![self.diagnostics(&uri)]
+                    }
+                    "textDocument/didClose" => {
+                        self.documents.remove(&uri);
+                        vec![notification(
+                            "textDocument/publishDiagnostics",
+                            json!({"uri": uri, "diagnostics": []}),
+                        )]
+                    }
+                    _ => vec![],
+                };
+            }
+        };
+        let result = match method {
+            "initialize" => json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                    "documentFormattingProvider": true,
+                },
+                "serverInfo": {"name": "transducer-lsp"},
+            }),
+            "shutdown" => Value::Null,
+            "textDocument/hover" => self.hover(&uri, &params["position"]),
+            "textDocument/definition" => self.definition(&uri, &params["position"]),
+            "textDocument/formatting" => self.formatting(&uri),
+            _ => {
+                return vec![json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": {"code": -32601, "message": format!("unknown method `{}`", method)},
+                })]
+            }
+        };
+        vec![json!({"jsonrpc": "2.0", "id": id, "result": result})]
+    }
+
+    fn diagnostics(&self, uri: &str) -> Value {
+        let text = self.text(uri);
+        let diagnostics = match parse(text, syntax(uri)) {
+            Ok(_) => vec![],
+            Err(diagnostics) => diagnostics,
+        };
+        let diagnostics: Vec<Value> = diagnostics
+            .iter()
+            .map(|diagnostic| {
+                let mut message = diagnostic.message.clone();
+                if let Some(help) = &diagnostic.help {
+                    message.push_str(&format!("\nhelp: {}", help));
+                }
+                json!({
+                    "range": range(text, diagnostic.span),
+                    "severity": 1,
+                    "source": "transducer",
+                    "message": message,
+                })
+            })
+            .collect();
+        notification(
+            "textDocument/publishDiagnostics",
+            json!({"uri": uri, "diagnostics": diagnostics}),
+        )
+    }
+
+    // The label paths and the arities of the outputs of the hovered
+    // `print`, one line per output
+    fn hover(&self, uri: &str, position: &Value) -> Value {
+        let text = self.text(uri);
+        let syntax = syntax(uri);
+        let Ok((tokens, stages)) = parse(text, syntax) else {
+            return Value::Null;
+        };
+        let Some(index) = token_at(&tokens, location(text, position)) else {
+            return Value::Null;
+        };
+        if tokens[index].token != Token::Print {
+            return Value::Null;
+        }
+        let mut lines = Vec::new();
+        for (number, stage) in stages.iter().enumerate() {
+            let Ok(stmts) = compiled(stage) else {
+                return Value::Null;
+            };
+            let (labels, arities) = outputs(&stmts, &stage.alphabet);
+            // the copies of the print made by unrolling and inlining
+            let mut paths = Vec::new();
+            print_paths(&stmts, tokens[index].span, &mut Vec::new(), &mut paths);
+            for path in paths {
+                if let Some(output) = labels.iter().position(|label| *label == path) {
+                    let stage = if stages.len() > 1 {
+                        format!(" in stage {}", number + 1)
+                    } else {
+                        String::new()
+                    };
+                    lines.push(format!(
+                        "- label `{:?}`, arity {}{}",
+                        path, arities[output], stage
+                    ));
+                }
+            }
+        }
+        if lines.is_empty() {
+            return Value::Null;
+        }
+        json!({
+            "contents": {"kind": "markdown", "value": lines.join("\n")},
+            "range": range(text, tokens[index].span),
+        })
+    }
+
+    // The place where the variable under the cursor is bound
+    fn definition(&self, uri: &str, position: &Value) -> Value {
+        let text = self.text(uri);
+        let syntax = syntax(uri);
+        let Ok(tokens) = tokenize_with(text, syntax) else {
+            return Value::Null;
+        };
+        let Some(index) = token_at(&tokens, location(text, position)) else {
+            return Value::Null;
+        };
+        let span = tokens[index].span;
+        let Ok(source) = Parser::new(tokens).parse_source(syntax) else {
+            return Value::Null;
+        };
+        // the checker resolves the variables in the scopes of the source
+        let (bindings, _) = check(&source);
+        match bindings.get(&span) {
+            Some(binding) => json!({"uri": uri, "range": range(text, *binding)}),
+            None => Value::Null,
+        }
+    }
+
+    // The whole document replaced by its formatted text, when it is in
+    // the brace syntax and parses
+    fn formatting(&self, uri: &str) -> Value {
+        let text = self.text(uri);
+        if syntax(uri) != Syntax::Braces {
+            return Value::Null;
+        }
+        let Ok(tokens) = tokenize_with(text, Syntax::Braces) else {
+            return Value::Null;
+        };
+        let Ok(source) = Parser::new(tokens).parse_source(Syntax::Braces) else {
+            return Value::Null;
+        };
+        let formatted = format_source(&source);
+        if formatted == text {
+            return json!([]);
+        }
+        let lines = text.split('\n').count();
+        json!([{
+            "range": {
+                "start": {"line": 0, "character": 0},
+                "end": {"line": lines, "character": 0},
+            },
+            "newText": formatted,
+        }])
+    }
+
+    fn text(&self, uri: &str) -> &str {
+        self.documents
+            .get(uri)
+            .map(String::as_str)
+            .unwrap_or_default()
+    }
+}
+
+fn notification(method: &str, params: Value) -> Value {
+    json!({"jsonrpc": "2.0", "method": method, "params": params})
+}
+
+// `.for` documents are written in the paper syntax
+fn syntax(uri: &str) -> Syntax {
+    Syntax::of_file(uri)
+}
+
+fn parse(text: &str, syntax: Syntax) -> Result<(Vec<SpannedToken>, Vec<Program>), Vec<Diagnostic>> {
+    let tokens = tokenize_with(text, syntax)?;
+    let mut parser = Parser::new(tokens.clone());
+    let stages = match syntax {
+        Syntax::Braces => parser.parse_pipeline()?,
+        Syntax::Paper => vec![parser.parse_paper()?],
+    };
+    Ok((tokens, stages))
+}
+
+// The statements labelled by the compilation
+fn compiled(program: &Program) -> Result<Vec<Stmt>, CompileError> {
+    Ok(eliminate_flags(&eliminate_breaks(&unroll(&program.stmts)?))?)
+}
+
+// The label path and the arity of every output of the statements
+fn outputs(stmts: &[Stmt], alphabet: &Alphabet) -> (Vec<Vec<usize>>, Vec<usize>) {
+    let mut labels = Vec::new();
+    let mut universe_formulas = Vec::new();
+    traverse_and_label(
+        stmts,
+        &mut Vec::new(),
+        &mut labels,
+        None,
+        &mut universe_formulas,
+        &mut Vec::new(),
+        &mut Vec::new(),
+        &mut Vec::new(),
+        alphabet,
+    );
+    let arities = universe_formulas
+        .iter()
+        .map(|(vars, _)| vars.len())
+        .collect();
+    (labels, arities)
+}
+
+// The label paths of the outputs of the print statement at `span`, the
+// labels being the indices of the statements from the outermost block
+fn print_paths(stmts: &[Stmt], span: Span, path: &mut Vec<usize>, paths: &mut Vec<Vec<usize>>) {
+    for (index, stmt) in stmts.iter().enumerate() {
+        match stmt {
+            Stmt::Print(_, print) if print.start == span.start => {
+                let mut output = path.clone();
+                output.push(index);
+                paths.push(output);
+            }
+            Stmt::For0(_, body) | Stmt::For1(_, body) | Stmt::If(_, body) => {
+                path.push(index);
+                print_paths(body, span, path, paths);
+                path.pop();
+            }
+            _ => {}
+        }
+    }
+}
+
+// The token at a location of the source
+fn token_at(tokens: &[SpannedToken], location: Location) -> Option<usize> {
+    tokens
+        .iter()
+        .position(|token| token.span.start <= location && location < token.span.end)
+}
+
+// The location of a position of the protocol, whose lines start at 0 and
+// whose characters are counted in UTF-16 code units
+fn location(text: &str, position: &Value) -> Location {
+    let line = position["line"].as_u64().unwrap_or_default() as usize;
+    let character = position["character"].as_u64().unwrap_or_default() as usize;
+    let mut units = 0;
+    let column = text
+        .split('\n')
+        .nth(line)
+        .unwrap_or_default()
+        .chars()
+        .take_while(|c| {
+            units += c.len_utf16();
+            units <= character
+        })
+        .count();
+    Location::new(line + 1, column + 1)
+}
+
+fn position(text: &str, location: Location) -> Value {
+    let character: usize = text
+        .split('\n')
+        .nth(location.line - 1)
+        .unwrap_or_default()
+        .chars()
+        .take(location.column - 1)
+        .map(char::len_utf16)
+        .sum();
+    json!({"line": location.line - 1, "character": character})
+}
+
+fn range(text: &str, span: Span) -> Value {
+    json!({"start": position(text, span.start), "end": position(text, span.end)})
+}
@@ -1,88 +1,99 @@
 use crate::ast::Bexpr;
 
+/// Generate the order formula between every pair of print statements.
+/// The universe formulas must range over the loop variables of the program,
+/// and `for0_or_for1` gives the direction of the loop binding each of them.
+/// The order formula for (i, j) has free variables x1, ..., x_arity(i)
+/// and y1, ..., y_arity(j).
 pub fn generate_order_formula(
     universe_formulas: &[(Vec<String>, Bexpr)],
-    for0_or_for1: &[usize],
+    for0_or_for1: &[(String, usize)],
     order_formulas: &mut Vec<(usize, usize, Bexpr)>,
 ) {
-    // Convert vector of vector of strings to vector of vector of i32
-    let for_vars: Vec<Vec<i32>> = universe_formulas
-        .iter()
-        .map(|(vars, _)| {
-            vars.iter()
-                .map(|var| var[1..].parse::<i32>().unwrap())
-                .collect()
-        })
-        .collect();
+    let for_vars: Vec<&Vec<String>> = universe_formulas.iter().map(|(vars, _)| vars).collect();
+    let direction = |var: &String| {
+        for0_or_for1
+            .iter()
+            .find(|(loop_var, _)| loop_var == var)
+            .map(|(_, dir)| *dir)
+            .unwrap_or(0)
+    };
 
     // Logic for generating order formulas
     for i in 0..universe_formulas.len() {
         for j in 0..universe_formulas.len() {
             //store the largest common prefix array for each of the arrays for_vars[i] and for_vars[j]
             let mut lcp = Vec::new();
-            let min_len = std::cmp::min(for_vars[i].len(), for_vars[j].len());
-            for k in 0..min_len {
-                if for_vars[i][k] == for_vars[j][k] {
-                    lcp.push(for_vars[i][k]);
+            for (var_i, var_j) in for_vars[i].iter().zip(for_vars[j].iter()) {
+                if var_i == var_j {
+                    lcp.push(var_i);
                 } else {
                     break;
                 }
             }
 
-            // Generate the order formula by iterating backwards through the LCP array
-            let mut order_formula = if i <= j {
-                Bexpr::Var("T".to_string())
-            } else {
-                Bexpr::Var("F".to_string())
-            };
+            // the k-th loop variable is the (k+1)-th free variable on both sides
+            let loops: Vec<(String, String, usize)> = lcp
+                .iter()
+                .enumerate()
+                .map(|(k, var)| (format!("x{}", k + 1), format!("y{}", k + 1), direction(var)))
+                .collect();
+            let order_formula = lexicographic_order(&loops, i <= j);
 
-            for k in (0..lcp.len()).rev() {
-                let lhs = Bexpr::Var(format!("x{}", lcp[k])).clone(); // Cloning to avoid move
-                let rhs = Bexpr::Var(format!("y{}", lcp[k])).clone(); // Cloning to avoid move
+            // Now order_formula contains the complete nested order conditions
 
-                let less = Bexpr::Less(Box::new(lhs.clone()), Box::new(rhs.clone()));
-                let greater = Bexpr::Greater(Box::new(lhs.clone()), Box::new(rhs.clone()));
-                let greater_equal =
-                    Bexpr::GreaterEqual(Box::new(lhs.clone()), Box::new(rhs.clone()));
-                let less_equal = Bexpr::LessEqual(Box::new(lhs.clone()), Box::new(rhs.clone()));
-                let equal = Bexpr::Equal(Box::new(lhs), Box::new(rhs));
+            order_formulas.push((i, j, order_formula));
+        }
+    }
+}
 
-                let combined_condition = if k == lcp.len() - 1 {
-                    // Last element of LCP: combine with just the less condition
-                    if for0_or_for1[k] == 0 {
-                        if i <= j {
-                            less_equal
-                        } else {
-                            less
-                        }
-                    } else {
-                        if i <= j {
-                            greater_equal
-                        } else {
-                            greater
-                        }
-                    }
-                } else {
-                    // Not the last element: combine with the OR of less and equal conditions
-                    if for0_or_for1[k] == 0 {
-                        Bexpr::Or(
-                            Box::new(less),
-                            Box::new(Bexpr::And(Box::new(equal), Box::new(order_formula.clone()))),
-                        )
-                    } else {
-                        Bexpr::Or(
-                            Box::new(greater),
-                            Box::new(Bexpr::And(Box::new(equal), Box::new(order_formula.clone()))),
-                        )
-                    }
-                };
+/// Compare the iterations (lhs_1, ..., lhs_k) and (rhs_1, ..., rhs_k) of k
+/// nested loops, each given as (lhs, rhs, direction of the loop).
+/// The formula holds when the left iteration comes first; when both are the
+/// same iteration it holds if and only if `or_equal` is set.
+pub fn lexicographic_order(loops: &[(String, String, usize)], or_equal: bool) -> Bexpr {
+    // Generate the order formula by iterating backwards through the loops
+    let mut order_formula = if or_equal {
+        Bexpr::True
+    } else {
+        Bexpr::False
+    };
 
-                order_formula = combined_condition;
-            }
+    for k in (0..loops.len()).rev() {
+        let (lhs, rhs, direction) = &loops[k];
+        let lhs = Bexpr::Var(lhs.clone());
+        let rhs = Bexpr::Var(rhs.clone());
 
-            // Now order_formula contains the complete nested order conditions
+        let less = Bexpr::Less(Box::new(lhs.clone()), Box::new(rhs.clone()));
+        let greater = Bexpr::Greater(Box::new(lhs.clone()), Box::new(rhs.clone()));
+        let greater_equal = Bexpr::GreaterEqual(Box::new(lhs.clone()), Box::new(rhs.clone()));
+        let less_equal = Bexpr::LessEqual(Box::new(lhs.clone()), Box::new(rhs.clone()));
+        let equal = Bexpr::Equal(Box::new(lhs), Box::new(rhs));
 
-            order_formulas.push((i, j, order_formula));
-        }
+        let combined_condition = if k == loops.len() - 1 {
+            // Innermost loop: combine with just the less condition
+            if *direction == 0 {
+                if or_equal {
+                    less_equal
+                } else {
+                    less
+                }
+            } else if or_equal {
+                greater_equal
+            } else {
+                greater
+            }
+        } else {
+            // Not the innermost loop: combine with the OR of less and equal conditions
+            let strictly_before = if *direction == 0 { less } else { greater };
+            Bexpr::Or(
+                Box::new(strictly_before),
+                Box::new(Bexpr::And(Box::new(equal), Box::new(order_formula))),
+            )
+        };
+
+        order_formula = combined_condition;
     }
+
+    order_formula
 }
@@ -1,8 +1,10 @@
 use crate::bexpr_evaluator;
 ///
-/// In this file we define what is a quantifier
-/// free interpretation of words.
+/// In this file we define what is a first-order
+/// interpretation of words (quantifier free unless
+/// the program quantifies over positions).
 ///
+use crate::ast::InputMode;
 use crate::Bexpr;
 use std::collections::HashMap;
 
@@ -24,9 +26,10 @@ pub struct OutputPosition {
     vars: Vec<InputPosition>,
 }
 
-/// A quantifier free interpretation of words
+/// A first-order interpretation of words: the formulas
+/// may quantify over the input positions
 #[derive(Debug, Clone)]
-pub struct QfInterpretation {
+pub struct FoInterpretation {
     /// Display information for the labels
     pub labels: Vec<String>,
     /// arities of the labels (number of free variables)
@@ -53,23 +56,22 @@ pub struct QfInterpretation {
 /// x1, x2, x3, ... replaced by the corresponding positions (0,1,3)
 fn evaluate_formula(
     formula: &Bexpr,
-    word: String,
+    word: &[String],
     variables: &[(String, &OutputPosition)],
 ) -> bool {
     let variables_environment: HashMap<String, InputPosition> = variables
         .iter()
-        .map(|(variable_name, pos)| {
+        .flat_map(|(variable_name, pos)| {
             pos.vars
                 .iter()
                 .enumerate()
                 .map(|(i, v)| (format!("{variable_name}{}", i+1), *v))
                 .collect::<Vec<(String, InputPosition)>>()
         })
-        .flatten()
         .collect();
 
     let mut evaluator = bexpr_evaluator::Evaluator {
-        word: word.clone(),
+        word: word.to_vec(),
         variables: variables_environment,
     };
 
@@ -77,7 +79,7 @@ fn evaluate_formula(
 }
 
 #[derive(Debug)]
-pub enum QfInterpretationError {
+pub enum FoInterpretationError {
     MissingUniverseFormula {
         label: Label,
     },
@@ -86,17 +88,23 @@ pub enum QfInterpretationError {
         label_b: Label,
     },
     NoLetter {
-        word: String,
+        word: Vec<String>,
         position: OutputPosition,
     },
     TooManyLetters {
-        word: String,
+        word: Vec<String>,
         position: OutputPosition,
         values: Vec<Letter>,
     },
 }
 
-impl QfInterpretation {
+impl Default for FoInterpretation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FoInterpretation {
     pub fn new() -> Self {
         Self {
             labels: Vec::new(),
@@ -109,16 +117,16 @@ impl QfInterpretation {
 
     pub fn get_letter(
         &self,
-        word: String,
+        word: &[String],
         position: &OutputPosition,
-    ) -> Result<Letter, QfInterpretationError> {
+    ) -> Result<Letter, FoInterpretationError> {
         let possible_letters = self
             .letters
             .iter()
             .filter_map(|(l, letter, phi)| {
                 if *l == position.label {
                     let variables = vec![("x".to_string(), position)];
-                    if evaluate_formula(phi, word.clone(), &variables) {
+                    if evaluate_formula(phi, word, &variables) {
                         Some(letter.clone())
                     } else {
                         None
@@ -130,13 +138,13 @@ impl QfInterpretation {
             .collect::<Vec<Letter>>();
     
         match possible_letters.len() {
-            0 => Err(QfInterpretationError::NoLetter {
-                word,
+            0 => Err(FoInterpretationError::NoLetter {
+                word: word.to_vec(),
                 position: position.clone(),
             }),
             1 => Ok(possible_letters[0].clone()),
-            _ => Err(QfInterpretationError::TooManyLetters {
-                word,
+            _ => Err(FoInterpretationError::TooManyLetters {
+                word: word.to_vec(),
                 position: position.clone(),
                 values: possible_letters,
             }),
@@ -145,45 +153,45 @@ impl QfInterpretation {
 
     pub fn get_order(
         &self,
-        word: String,
+        word: &[String],
         position_a: &OutputPosition,
         position_b: &OutputPosition,
-    ) -> Result<bool, QfInterpretationError> {
+    ) -> Result<bool, FoInterpretationError> {
         let formula = self
             .order
             .iter()
             .find(|(i, j, _)| *i == position_a.label && *j == position_b.label)
             .map(|(_, _, phi)| phi)
-            .ok_or(QfInterpretationError::MissingOrderFormula {
+            .ok_or(FoInterpretationError::MissingOrderFormula {
                 label_a: position_a.label,
                 label_b: position_b.label,
             })?;
 
         let variables = vec![("x".to_string(), position_a), ("y".to_string(), position_b)];
-        Ok(evaluate_formula(formula, word.clone(), &variables))
+        Ok(evaluate_formula(formula, word, &variables))
     }
 
     pub fn get_universe(
         &self,
-        word: String,
+        word: &[String],
         position: &OutputPosition,
-    ) -> Result<bool, QfInterpretationError> {
+    ) -> Result<bool, FoInterpretationError> {
         let formula = self
             .universe
             .iter()
             .find(|(i, _)| *i == position.label)
             .map(|(_, phi)| phi)
-            .ok_or(QfInterpretationError::MissingUniverseFormula {
+            .ok_or(FoInterpretationError::MissingUniverseFormula {
                 label: position.label,
             })?;
 
         let variables = vec![("x".to_string(), position)];
 
-        Ok(evaluate_formula(formula, word.clone(), &variables))
+        Ok(evaluate_formula(formula, word, &variables))
     }
 }
 
-pub fn print_interpretation(qf: &QfInterpretation, for_vars: &Vec<Vec<i32>>) {
+pub fn print_interpretation(qf: &FoInterpretation, for_vars: &[Vec<i32>]) {
     //print the labels
     println!("Labels: {:?}", qf.labels);
 
@@ -201,12 +209,12 @@ pub fn print_interpretation(qf: &QfInterpretation, for_vars: &Vec<Vec<i32>>) {
     for (i, j, formula) in qf.order.iter() {
         let mut vec = Vec::new();
 
-        for a in 0..for_vars[*i].len() {
-            vec.push(format!("X{}", for_vars[*i][a]));
+        for var in &for_vars[*i] {
+            vec.push(format!("X{}", var));
         }
 
-        for a in 0..for_vars[*j].len() {
-            vec.push(format!("x{}", for_vars[*j][a]));
+        for var in &for_vars[*j] {
+            vec.push(format!("x{}", var));
         }
 
         println!("{i} <= {j}: {formula}")
@@ -218,91 +226,21 @@ pub fn print_interpretation(qf: &QfInterpretation, for_vars: &Vec<Vec<i32>>) {
     }
 }
 
-fn create_example_interpretation() -> QfInterpretation {
-    QfInterpretation {
-        labels: vec!["l1".to_string(), "l2".to_string()],
-        arities: vec![1, 2],
-        universe: vec![
-            (0, Bexpr::Str("true".into())),
-            (
-                1,
-                Bexpr::LessEqual(
-                    Box::new(Bexpr::Var("x1".into())),
-                    Box::new(Bexpr::Var("x2".into())),
-                ),
-            ),
-        ],
-        letters: vec![
-            (0, "a".to_string(), Bexpr::Str("true".into())),
-            (0, "b".to_string(), Bexpr::Str("false".into())),
-            (
-                1,
-                "a".to_string(),
-                Bexpr::Equal(
-                    Box::new(Bexpr::Var("x1".into())),
-                    Box::new(Bexpr::Var("x2".into())),
-                ),
-            ),
-            (
-                1,
-                "b".to_string(),
-                Bexpr::NotEqual(
-                    Box::new(Bexpr::Var("x1".into())),
-                    Box::new(Bexpr::Var("x2".into())),
-                ),
-            ),
-        ],
-        order: vec![
-            (
-                0,
-                0,
-                Bexpr::LessEqual(
-                    Box::new(Bexpr::Var("x1".into())),
-                    Box::new(Bexpr::Var("y1".into())),
-                ),
-            ),
-            (0, 1, Bexpr::Str("true".into())),
-            (1, 0, Bexpr::Str("false".into())),
-            (
-                1,
-                1,
-                // lexicographic order "x1 < x2 or x1 = x2 and y1 >= y2"
-                Bexpr::Or(
-                    Box::new(Bexpr::LessEqual(
-                        Box::new(Bexpr::Var("x1".into())),
-                        Box::new(Bexpr::Var("x2".into())),
-                    )),
-                    Box::new(Bexpr::And(
-                        Box::new(Bexpr::Equal(
-                            Box::new(Bexpr::Var("x1".into())),
-                            Box::new(Bexpr::Var("x2".into())),
-                        )),
-                        Box::new(Bexpr::LessEqual(
-                            Box::new(Bexpr::Var("y2".into())),
-                            Box::new(Bexpr::Var("y1".into())),
-                        )),
-                    )),
-                ),
-            ),
-        ],
-    }
-}
-
-/// Should produce a "valid" QfInterpretation
+/// Should produce a "valid" FoInterpretation
 /// meaning that
 ///
 /// 1. size of arities = size of labels
 /// 2. for every label, the number of free variables in the formula
-///   is equal to the arity of the label
+///    is equal to the arity of the label
 pub fn fit_interpretation(
     universe_formulas: Vec<(Vec<String>, Bexpr)>,
     order_formulas: Vec<(usize, usize, Bexpr)>,
     for_vars: Vec<Vec<i32>>,
     labels: Vec<Vec<usize>>,
-    label_formulas: Vec<(String, String, String)>,
-) -> QfInterpretation {
+    label_formulas: Vec<Vec<(Letter, Bexpr)>>,
+) -> FoInterpretation {
     //define the new interpretation
-    let mut qf = QfInterpretation::new();
+    let mut qf = FoInterpretation::new();
 
     //define the arities
     qf.arities = for_vars.iter().map(|vars| vars.len()).collect();
@@ -341,34 +279,39 @@ pub fn fit_interpretation(
     qf.letters = vec![];
     i = 0;
 
-    for (label_formula_a, label_formula_b, label_formula_hash) in label_formulas {
-        //push bexpr of the label formulas
-        qf.letters
-            .push((i, "a".to_string(), Bexpr::Str(label_formula_a)));
-        qf.letters
-            .push((i, "b".to_string(), Bexpr::Str(label_formula_b)));
-        qf.letters
-            .push((i, "#".to_string(), Bexpr::Str(label_formula_hash)));
+    for letter_formulas in label_formulas {
+        //push bexpr of the label formulas, one for every output letter
+        for (letter, formula) in letter_formulas {
+            qf.letters.push((i, letter, formula));
+        }
         i += 1;
     }
 
     qf
 }
 
-/// input: qf : QfInterpretation, w : String
+/// input: qf : FoInterpretation, w : String
 ///
 /// 1. produce all the tuples of positions in the string
-///     based on the arities of the labels in the qf interpretation
+///    based on the arities of the labels in the qf interpretation
 ///
 /// 2. for each tuple of position, evaluate the corresponding domain
-/// formula, and if it is false, remove this tuple from the list.
+///    formula, and if it is false, remove this tuple from the list.
 ///
-/// 3.  Sort the list of tuples of positions according to the formula <=
+/// 3. Sort the list of tuples of positions according to the formula <=
 ///
 /// 4. For each tuple of positions, evaluate the corresponding letter
-/// formula and replace the position by the result of the evaluation.
+///    formula and replace the position by the result of the evaluation.
 ///
-pub fn evaluate(qf: &QfInterpretation, w: String) -> String {
+pub fn evaluate(qf: &FoInterpretation, w: String) -> String {
+    // every Unicode scalar value of the word is a letter
+    let letters: Vec<Letter> = InputMode::Scalars.split(&w);
+    InputMode::Scalars.join_word(&evaluate_word(qf, &letters))
+}
+
+/// Evaluate the interpretation on a word of letters of any length (see
+/// `Alphabet::split_word`), returning the letters of the output
+pub fn evaluate_word(qf: &FoInterpretation, w: &[Letter]) -> Vec<Letter> {
     // the size of the universe
     let word_size = w.len();
 
@@ -393,8 +336,7 @@ pub fn evaluate(qf: &QfInterpretation, w: String) -> String {
         .iter()
         .enumerate()
         .flat_map(|(label, &arity)| {
-            std::iter::repeat(0..word_size)
-                .take(arity)
+            std::iter::repeat_n(0..word_size, arity)
                 .multi_cartesian_product()
                 .map(move |vars| OutputPosition { label, vars })
         })
@@ -405,7 +347,7 @@ pub fn evaluate(qf: &QfInterpretation, w: String) -> String {
     // filter the universe based on the universe formulas
     let universe: Vec<OutputPosition> = universe
         .into_iter()
-        .filter(|pos| qf.get_universe(w.clone(), &pos).unwrap())
+        .filter(|pos| qf.get_universe(w, pos).unwrap())
         .collect();
     //eprintln!("Universe [filtered]: {:?}", universe);
 
@@ -414,7 +356,7 @@ pub fn evaluate(qf: &QfInterpretation, w: String) -> String {
     // FIXME: if equal then say equal
     let universe: Vec<OutputPosition> = universe
         .into_iter()
-        .sorted_by(|a, b| match qf.get_order(w.clone(), &a, &b).unwrap() {
+        .sorted_by(|a, b| match qf.get_order(w, a, b).unwrap() {
             true => std::cmp::Ordering::Less,
             false => std::cmp::Ordering::Greater,
         })
@@ -423,9 +365,9 @@ pub fn evaluate(qf: &QfInterpretation, w: String) -> String {
 
     // evaluate the letter formulas and replace the positions
     // by the result of the evaluation
-    let result: String = universe
+    let result: Vec<Letter> = universe
         .iter()
-        .map(|pos| qf.get_letter(w.clone(), pos).unwrap())
+        .map(|pos| qf.get_letter(w, pos).unwrap())
         .collect();
     //eprintln!("Universe [replaced]: {:?}", universe);
 
@@ -436,19 +378,91 @@ pub fn evaluate(qf: &QfInterpretation, w: String) -> String {
 mod tests {
     use super::*;
 
+    fn create_example_interpretation() -> FoInterpretation {
+        FoInterpretation {
+            labels: vec!["l1".to_string(), "l2".to_string()],
+            arities: vec![1, 2],
+            universe: vec![
+                (0, Bexpr::Str("true".into())),
+                (
+                    1,
+                    Bexpr::LessEqual(
+                        Box::new(Bexpr::Var("x1".into())),
+                        Box::new(Bexpr::Var("x2".into())),
+                    ),
+                ),
+            ],
+            letters: vec![
+                (0, "a".to_string(), Bexpr::Str("true".into())),
+                (0, "b".to_string(), Bexpr::Str("false".into())),
+                (
+                    1,
+                    "a".to_string(),
+                    Bexpr::Equal(
+                        Box::new(Bexpr::Var("x1".into())),
+                        Box::new(Bexpr::Var("x2".into())),
+                    ),
+                ),
+                (
+                    1,
+                    "b".to_string(),
+                    Bexpr::NotEqual(
+                        Box::new(Bexpr::Var("x1".into())),
+                        Box::new(Bexpr::Var("x2".into())),
+                    ),
+                ),
+            ],
+            order: vec![
+                (
+                    0,
+                    0,
+                    Bexpr::LessEqual(
+                        Box::new(Bexpr::Var("x1".into())),
+                        Box::new(Bexpr::Var("y1".into())),
+                    ),
+                ),
+                (0, 1, Bexpr::Str("true".into())),
+                (1, 0, Bexpr::Str("false".into())),
+                (
+                    1,
+                    1,
+                    // lexicographic order "x1 < y1 or x1 = y1 and x2 <= y2"
+                    Bexpr::Or(
+                        Box::new(Bexpr::Less(
+                            Box::new(Bexpr::Var("x1".into())),
+                            Box::new(Bexpr::Var("y1".into())),
+                        )),
+                        Box::new(Bexpr::And(
+                            Box::new(Bexpr::Equal(
+                                Box::new(Bexpr::Var("x1".into())),
+                                Box::new(Bexpr::Var("y1".into())),
+                            )),
+                            Box::new(Bexpr::LessEqual(
+                                Box::new(Bexpr::Var("x2".into())),
+                                Box::new(Bexpr::Var("y2".into())),
+                            )),
+                        )),
+                    ),
+                ),
+            ],
+        }
+    }
+
     #[test]
     fn test_evaluate() {
+        // every position prints an `a`, followed by the pairs x1 <= x2 in
+        // lexicographic order, printing `a` on the diagonal and `b` elsewhere
         let qf = create_example_interpretation();
         let result = evaluate(&qf, "abab".to_string());
         eprintln!("Result: {}", result);
-        assert_eq!(result, "ab");
+        assert_eq!(result, "aaaaabbbabbaba");
         let result = evaluate(&qf, "acab".to_string());
-        assert_eq!(result, "ab");
+        assert_eq!(result, "aaaaabbbabbaba");
         let result = evaluate(&qf, "aaaa".to_string());
-        assert_eq!(result, "ab");
+        assert_eq!(result, "aaaaabbbabbaba");
         let result = evaluate(&qf, "".to_string());
         assert_eq!(result, "");
         let result = evaluate(&qf, "a".to_string());
-        assert_eq!(result, "a");
+        assert_eq!(result, "aa");
     }
 }
@@ -1,45 +1,84 @@
 use crate::ast::Bexpr;
-use crate::qf_interpretation::QfInterpretation;
+use crate::lexer::LENGTH_VARIABLE;
+use crate::qf_interpretation::FoInterpretation;
 use crate::two_sorted_formulas::{FormulaF, FormulaR};
+use std::fmt;
+
+/// A formula of the interpretation that has no translation to the
+/// solver formulas
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PullbackError {
+    /// A boolean variable that was not compiled away
+    Flag(String),
+    /// An offset or a constant outside of a comparison of positions
+    Position(String),
+    /// A string that is not a letter at a position
+    Letter(String),
+    /// A comparison of terms that are neither positions nor a label
+    /// and a letter, such as two labels left unexpanded
+    Comparison(String),
+    /// A label outside of a comparison
+    Label(String),
+}
 
-pub fn bexpr_to_formula_s(bexpr: &Bexpr) -> FormulaS {
-    match bexpr {
+impl fmt::Display for PullbackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PullbackError::Flag(flag) => write!(
+                f,
+                "boolean variable {} is still read in the interpretation",
+                flag
+            ),
+            PullbackError::Position(term) => write!(
+                f,
+                "position {} is used as a condition instead of being compared",
+                term
+            ),
+            PullbackError::Letter(string) => {
+                write!(f, "string {} is not a letter at a position", string)
+            }
+            PullbackError::Comparison(comparison) => {
+                write!(f, "comparison {} has no translation", comparison)
+            }
+            PullbackError::Label(label) => write!(
+                f,
+                "label {} is used as a condition instead of being compared",
+                label
+            ),
+        }
+    }
+}
+
+pub fn bexpr_to_formula_s(bexpr: &Bexpr) -> Result<FormulaS, PullbackError> {
+    // Comparisons with offsets and constants are expressed with the
+    // successor relation and the first and last positions
+    if let Some(formula) = offset_comparison(bexpr) {
+        return Ok(formula);
+    }
+
+    Ok(match bexpr {
         Bexpr::Var(var_name) => {
-            if var_name == "T" {
-                FormulaS::const_true()
-            } else if var_name == "F" {
-                FormulaS::const_false()
-            } else {
-                // Handle variable expressions; assume Sort::Position for variables.
-                FormulaS {
-                    inside: FormulaF::Equal(
-                        crate::two_sorted_formulas::Sort::Position,
-                        var_name.clone(),
-                        var_name.clone(),
-                    ),
-                }
+            // Handle variable expressions; assume Sort::Position for variables.
+            FormulaS {
+                inside: FormulaF::Equal(
+                    crate::two_sorted_formulas::Sort::Position,
+                    var_name.clone(),
+                    var_name.clone(),
+                ),
             }
         }
 
+        Bexpr::True => FormulaS::const_true(),
+        Bexpr::False => FormulaS::const_false(),
+
         Bexpr::Str(s) => {
-            // Handle string expressions; "T" for true and "F" for false.
-            match s.as_str() {
-                "T" => FormulaS {
-                    inside: FormulaF::True,
-                },
-                "F" => FormulaS {
-                    inside: FormulaF::False,
-                },
-                _ => {
-                    // Handle letter at position cases, assuming format is letter(var_name).
-                    if let Some((letter, var_name)) = parse_letter_at_pos(s) {
-                        FormulaS {
-                            inside: FormulaF::LetterAtPos(var_name.to_string(), letter.to_string()),
-                        }
-                    } else {
-                        unimplemented!("Unexpected string value");
-                    }
+            // Handle letter at position cases, assuming format is letter(var_name).
+            if let Some((letter, var_name)) = parse_letter_at_pos(s) {
+                FormulaS {
+                    inside: FormulaF::LetterAtPos(var_name.to_string(), letter.to_string()),
                 }
+            } else {
+                return Err(PullbackError::Letter(s.clone()));
             }
         }
 
@@ -77,7 +116,7 @@ pub fn bexpr_to_formula_s(bexpr: &Bexpr) -> FormulaS {
             } else if let Some((var1, var2)) = var_var {
                 FormulaS::equal(crate::two_sorted_formulas::Sort::Position, var1, var2)
             } else {
-                unimplemented!("Unexpected value for lhs and rhs");
+                return Err(PullbackError::Comparison(bexpr.to_string()));
             }
         }
 
@@ -98,7 +137,7 @@ pub fn bexpr_to_formula_s(bexpr: &Bexpr) -> FormulaS {
             } else if let Some((var1, var2)) = var_var {
                 FormulaS::equal(crate::two_sorted_formulas::Sort::Position, var1, var2).not()
             } else {
-                unimplemented!("Unexpected value for lhs and rhs");
+                return Err(PullbackError::Comparison(bexpr.to_string()));
             }
         }
 
@@ -123,21 +162,18 @@ pub fn bexpr_to_formula_s(bexpr: &Bexpr) -> FormulaS {
         Bexpr::Not(inner) => {
             // Handle negation.
             FormulaS {
-                inside: FormulaF::Not(Box::new(bexpr_to_formula_s(inner))),
+                inside: FormulaF::Not(Box::new(bexpr_to_formula_s(inner)?)),
             }
         }
 
-        Bexpr::Label(label) => {
-            // Handle label expressions.
-            unimplemented!("Labels shouldn't be directly part of Bexpr");
-        }
+        Bexpr::Label(_) => return Err(PullbackError::Label(bexpr.to_string())),
 
         Bexpr::And(lhs, rhs) => {
             // Handle logical AND.
             FormulaS {
                 inside: FormulaF::And(
-                    Box::new(bexpr_to_formula_s(lhs)),
-                    Box::new(bexpr_to_formula_s(rhs)),
+                    Box::new(bexpr_to_formula_s(lhs)?),
+                    Box::new(bexpr_to_formula_s(rhs)?),
                 ),
             }
         }
@@ -146,11 +182,167 @@ pub fn bexpr_to_formula_s(bexpr: &Bexpr) -> FormulaS {
             // Handle logical OR.
             FormulaS {
                 inside: FormulaF::Or(
-                    Box::new(bexpr_to_formula_s(lhs)),
-                    Box::new(bexpr_to_formula_s(rhs)),
+                    Box::new(bexpr_to_formula_s(lhs)?),
+                    Box::new(bexpr_to_formula_s(rhs)?),
                 ),
             }
         }
+
+        Bexpr::Exists(var, inner) => {
+            bexpr_to_formula_s(inner)?.exists(var.clone(), crate::two_sorted_formulas::Sort::Position)
+        }
+
+        Bexpr::Forall(var, inner) => {
+            bexpr_to_formula_s(inner)?.forall(var.clone(), crate::two_sorted_formulas::Sort::Position)
+        }
+
+        Bexpr::Flag(flag) => return Err(PullbackError::Flag(flag.clone())),
+
+        Bexpr::LetterIn(var_name, letters) => letters
+            .iter()
+            .map(|letter| FormulaS::letter_at_pos(var_name.clone(), letter.clone()))
+            .reduce(|left, right| left.or(right))
+            .unwrap_or_else(FormulaS::const_false),
+
+        Bexpr::Offset(_, _) | Bexpr::Number(_) => {
+            return Err(PullbackError::Position(bexpr.to_string()))
+        }
+    })
+}
+
+// Names of the first and last positions when a comparison mentions a
+// constant or the length of the word
+const FIRST_POSITION: &str = "pos_first";
+const LAST_POSITION: &str = "pos_last";
+
+// Translate `x + c1 op y + c2` when one of the sides has an offset or is
+// a constant, the constant `k` being the first position plus `k` and
+// `n + c` the last position plus `c + 1`
+fn offset_comparison(bexpr: &Bexpr) -> Option<FormulaS> {
+    let (lhs, rhs) = match bexpr {
+        Bexpr::LessEqual(lhs, rhs)
+        | Bexpr::Less(lhs, rhs)
+        | Bexpr::Equal(lhs, rhs)
+        | Bexpr::NotEqual(lhs, rhs)
+        | Bexpr::GreaterEqual(lhs, rhs)
+        | Bexpr::Greater(lhs, rhs) => (lhs, rhs),
+        _ => return None,
+    };
+    let is_arithmetic = |side: &Bexpr| match side {
+        Bexpr::Offset(_, _) | Bexpr::Number(_) => true,
+        Bexpr::Var(var_name) => var_name == LENGTH_VARIABLE,
+        _ => false,
+    };
+    if !is_arithmetic(lhs) && !is_arithmetic(rhs) {
+        return None;
+    }
+    let (x, left_offset) = try_extract_position_term(lhs)?;
+    let (y, right_offset) = try_extract_position_term(rhs)?;
+
+    // the comparison is `x + offset op y`
+    let offset = left_offset - right_offset;
+    let compare = |lv: i32, rv: i32| match bexpr {
+        Bexpr::LessEqual(_, _) => lv <= rv,
+        Bexpr::Less(_, _) => lv < rv,
+        Bexpr::GreaterEqual(_, _) => lv >= rv,
+        Bexpr::Greater(_, _) => lv > rv,
+        Bexpr::Equal(_, _) => lv == rv,
+        Bexpr::NotEqual(_, _) => lv != rv,
+        _ => unreachable!(),
+    };
+    let mut formula = match bexpr {
+        Bexpr::LessEqual(_, _) => offset_less_equal(&x, offset, &y),
+        Bexpr::Less(_, _) => offset_less_equal(&x, offset + 1, &y),
+        Bexpr::GreaterEqual(_, _) => offset_less_equal(&y, -offset, &x),
+        Bexpr::Greater(_, _) => offset_less_equal(&y, 1 - offset, &x),
+        Bexpr::Equal(_, _) | Bexpr::NotEqual(_, _) if x == y => {
+            if compare(offset, 0) {
+                FormulaS::const_true()
+            } else {
+                FormulaS::const_false()
+            }
+        }
+        Bexpr::Equal(_, _) => offset_equal(&x, offset, &y),
+        Bexpr::NotEqual(_, _) => offset_equal(&x, offset, &y).not(),
+        _ => unreachable!(),
+    };
+
+    // bind the first and last positions
+    let anchors = [x.as_str(), y.as_str()];
+    for (anchor, is_first) in [(FIRST_POSITION, true), (LAST_POSITION, false)] {
+        if anchors.contains(&anchor) {
+            let other = format!("{}_other", anchor);
+            let extremal = if is_first {
+                FormulaS::less_equal(anchor.to_string(), other.clone())
+            } else {
+                FormulaS::less_equal(other.clone(), anchor.to_string())
+            };
+            formula = extremal
+                .forall(other, crate::two_sorted_formulas::Sort::Position)
+                .and(formula)
+                .exists(anchor.to_string(), crate::two_sorted_formulas::Sort::Position);
+        }
+    }
+
+    // without position variables the empty word has no anchors, where
+    // the comparison is decided with `n = 0`
+    let is_anchor = |var: &str| var == FIRST_POSITION || var == LAST_POSITION;
+    if is_anchor(&x) && is_anchor(&y) {
+        let on_empty = |var: &str, offset: i32| if var == LAST_POSITION { offset - 1 } else { offset };
+        if compare(on_empty(&x, left_offset), on_empty(&y, right_offset)) {
+            let empty = FormulaS::const_false().forall(
+                format!("{}_any", FIRST_POSITION),
+                crate::two_sorted_formulas::Sort::Position,
+            );
+            formula = formula.or(empty);
+        }
+    }
+    Some(formula)
+}
+
+/// `x + offset = y`, as a chain of successors through fresh positions
+fn offset_equal(x: &str, offset: i32, y: &str) -> FormulaS {
+    if offset < 0 {
+        return offset_equal(y, -offset, x);
+    }
+    if offset == 0 {
+        return FormulaS::equal(
+            crate::two_sorted_formulas::Sort::Position,
+            x.to_string(),
+            y.to_string(),
+        );
+    }
+
+    let mut chain = vec![x.to_string()];
+    chain.extend((1..offset).map(|k| format!("{}_plus{}", x, k)));
+    chain.push(y.to_string());
+
+    let mut formula = FormulaS::successor(chain[0].clone(), chain[1].clone());
+    for pair in chain[1..].windows(2) {
+        formula = formula.and(FormulaS::successor(pair[0].clone(), pair[1].clone()));
+    }
+    for intermediate in chain[1..chain.len() - 1].iter().rev() {
+        formula = formula.exists(
+            intermediate.clone(),
+            crate::two_sorted_formulas::Sort::Position,
+        );
+    }
+    formula
+}
+
+/// `x + offset <= y`
+fn offset_less_equal(x: &str, offset: i32, y: &str) -> FormulaS {
+    if offset == 0 {
+        FormulaS::less_equal(x.to_string(), y.to_string())
+    } else if offset > 0 {
+        // the position x + offset exists and is before y
+        let shifted = format!("{}_plus{}", x, offset);
+        offset_equal(x, offset, &shifted)
+            .and(FormulaS::less_equal(shifted.clone(), y.to_string()))
+            .exists(shifted, crate::two_sorted_formulas::Sort::Position)
+    } else {
+        // x - k <= y if and only if not y + k + 1 <= x
+        offset_less_equal(y, 1 - offset, x).not()
     }
 }
 
@@ -171,6 +363,21 @@ fn try_extract_position_variable(bexpr: &Bexpr) -> Option<String> {
     }
 }
 
+// A position variable, possibly with an offset, where constants are
+// relative to the first position and `n` to the last one
+fn try_extract_position_term(bexpr: &Bexpr) -> Option<(String, i32)> {
+    match bexpr {
+        Bexpr::Var(var_name) if var_name == LENGTH_VARIABLE => Some((LAST_POSITION.to_string(), 1)),
+        Bexpr::Offset(var_name, offset) if var_name == LENGTH_VARIABLE => {
+            Some((LAST_POSITION.to_string(), offset + 1))
+        }
+        Bexpr::Number(k) => Some((FIRST_POSITION.to_string(), *k)),
+        Bexpr::Var(var_name) => Some((var_name.clone(), 0)),
+        Bexpr::Offset(var_name, offset) => Some((var_name.clone(), *offset)),
+        _ => None,
+    }
+}
+
 fn try_extract_label_variable(bexpr: &Bexpr) -> Option<String> {
     match bexpr {
         Bexpr::Label(var_name) => Some(var_name.clone()),
@@ -185,18 +392,17 @@ fn try_extract_str(bexpr: &Bexpr) -> Option<String> {
     }
 }
 
-fn parse_letter_at_pos(s: &str) -> Option<(String, String)> {
-    // This function expects the format to be letter(var_name), like a(x), b(y), #(z)
-    if let Some(open_paren_index) = s.find('(') {
-        if let Some(close_paren_index) = s.find(')') {
-            if close_paren_index > open_paren_index {
-                let letter = &s[..open_paren_index];
-                let var_name = &s[open_paren_index + 1..close_paren_index];
-                return Some((letter.to_string(), var_name.to_string()));
-            }
-        }
+pub(crate) fn parse_letter_at_pos(s: &str) -> Option<(String, String)> {
+    // This function expects the format to be letter(var_name), like a(x), b(y), #(z),
+    // the letter itself may be a parenthesis
+    let inside = s.strip_suffix(')')?;
+    let open_paren_index = inside.rfind('(')?;
+    if open_paren_index == 0 {
+        return None;
     }
-    None
+    let letter = &inside[..open_paren_index];
+    let var_name = &inside[open_paren_index + 1..];
+    Some((letter.to_string(), var_name.to_string()))
 }
 
 //
@@ -230,7 +436,16 @@ pub fn substitute_variables(formula: &Bexpr, name_x: &str, name_y: &str) -> Bexp
             Bexpr::Label(format!("{}{}", name_y, num_part))
         }
         Bexpr::Label(var_name) => Bexpr::Label(var_name.clone()), // for other variables
-        Bexpr::Str(_) => formula.clone(), // assuming Str does not need substitution
+        // letter formulas `a(x1)` are stored as strings
+        Bexpr::Str(s) => match parse_letter_at_pos(s) {
+            Some((letter, var_name)) if var_name.starts_with('x') => {
+                Bexpr::Str(format!("{}({}{})", letter, name_x, &var_name[1..]))
+            }
+            Some((letter, var_name)) if var_name.starts_with('y') => {
+                Bexpr::Str(format!("{}({}{})", letter, name_y, &var_name[1..]))
+            }
+            _ => formula.clone(),
+        },
         Bexpr::LessEqual(left, right) => Bexpr::LessEqual(
             Box::new(substitute_variables(left, name_x, name_y)),
             Box::new(substitute_variables(right, name_x, name_y)),
@@ -264,11 +479,42 @@ pub fn substitute_variables(formula: &Bexpr, name_x: &str, name_y: &str) -> Bexp
             Box::new(substitute_variables(left, name_x, name_y)),
             Box::new(substitute_variables(right, name_x, name_y)),
         ),
+        // bound variables never start with `x` or `y`
+        Bexpr::Exists(var, subexpr) => Bexpr::Exists(
+            var.clone(),
+            Box::new(substitute_variables(subexpr, name_x, name_y)),
+        ),
+        Bexpr::Forall(var, subexpr) => Bexpr::Forall(
+            var.clone(),
+            Box::new(substitute_variables(subexpr, name_x, name_y)),
+        ),
+        Bexpr::Offset(var_name, offset) if var_name.starts_with('x') => {
+            Bexpr::Offset(format!("{}{}", name_x, &var_name[1..]), *offset)
+        }
+        Bexpr::Offset(var_name, offset) if var_name.starts_with('y') => {
+            Bexpr::Offset(format!("{}{}", name_y, &var_name[1..]), *offset)
+        }
+        Bexpr::LetterIn(var_name, letters) if var_name.starts_with('x') => {
+            Bexpr::LetterIn(format!("{}{}", name_x, &var_name[1..]), letters.clone())
+        }
+        Bexpr::LetterIn(var_name, letters) if var_name.starts_with('y') => {
+            Bexpr::LetterIn(format!("{}{}", name_y, &var_name[1..]), letters.clone())
+        }
+        Bexpr::Offset(_, _)
+        | Bexpr::Number(_)
+        | Bexpr::LetterIn(_, _)
+        | Bexpr::Flag(_)
+        | Bexpr::True
+        | Bexpr::False => formula.clone(),
     }
 }
 
 /// TODO: implement
-pub fn universe_formula(qf: &QfInterpretation, label: usize, var_name: &str) -> FormulaS {
+pub fn universe_formula(
+    qf: &FoInterpretation,
+    label: usize,
+    var_name: &str,
+) -> Result<FormulaS, PullbackError> {
     // 1. find the correct formula (qf.letter.find (...))
     // 2. substitute the variables in the formula with x -> var
     // 3. return the formula
@@ -282,9 +528,7 @@ pub fn universe_formula(qf: &QfInterpretation, label: usize, var_name: &str) ->
                 substituted_formula
             );
 
-            let final_formula = bexpr_to_formula_s(&substituted_formula);
-
-            return final_formula;
+            return bexpr_to_formula_s(&substituted_formula);
         }
     }
 
@@ -293,12 +537,12 @@ pub fn universe_formula(qf: &QfInterpretation, label: usize, var_name: &str) ->
 
 /// TODO: implement
 pub fn order_formula(
-    qf: &QfInterpretation,
+    qf: &FoInterpretation,
     lx: usize,
     ly: usize,
     var_x: &str,
     var_y: &str,
-) -> FormulaS {
+) -> Result<FormulaS, PullbackError> {
     // 1. find the correct formula (qf.letter.find (...)) based on the labels
     // 2. substitute the variables in the formula with x -> var_x, y -> var_y
     // 3. return the formula
@@ -310,9 +554,7 @@ pub fn order_formula(
             let mut substituted_formula = formula.clone();
             substituted_formula = substitute_variables(&substituted_formula, var_x, var_y);
 
-            let final_formula = bexpr_to_formula_s(&substituted_formula);
-
-            return final_formula;
+            return bexpr_to_formula_s(&substituted_formula);
         }
     }
 
@@ -322,11 +564,11 @@ pub fn order_formula(
 /// TODO: implement
 // Function to find the letter formula and substitute variables
 pub fn letter_formula(
-    qf: &QfInterpretation,
+    qf: &FoInterpretation,
     l: usize,
     var: &str,
     letter: &str,
-) -> Option<FormulaS> {
+) -> Result<Option<FormulaS>, PullbackError> {
     // Find the correct formula from the `letters` vector
     let letter_entry = qf
         .letters
@@ -338,9 +580,9 @@ pub fn letter_formula(
         let substituted_formula = substitute_variables(formula, var, "");
 
         // Return the modified formula
-        Some(bexpr_to_formula_s(&substituted_formula))
+        bexpr_to_formula_s(&substituted_formula).map(Some)
     } else {
-        None
+        Ok(None)
     }
 }
 
@@ -352,10 +594,10 @@ pub fn letter_formula(
 //
 pub fn disjunction(vec: Vec<FormulaS>) -> FormulaS {
     // disjunction
-    if vec.len() == 0 {
-        return FormulaS {
+    if vec.is_empty() {
+        FormulaS {
             inside: FormulaF::False,
-        };
+        }
     } else {
         let mut v_iter = vec.into_iter();
         let mut f = v_iter.next().unwrap();
@@ -469,13 +711,34 @@ where
 
 type FormulaS = FormulaR<String, String>;
 
-pub fn pullback(post_condition: &FoFormula, qf: &QfInterpretation) -> FormulaS {
-    fold_fo_formula(post_condition, &|inner| pullback_unrec(inner, qf))
+pub fn pullback(
+    post_condition: &FoFormula,
+    qf: &FoInterpretation,
+) -> Result<FormulaS, PullbackError> {
+    fold_fo_formula(post_condition, &|inner| pullback_unrec(transpose(inner)?, qf))
+}
+
+// The formula with its translated subformulas, or the first error among them
+fn transpose<T, E>(formula: FoFormulaR<Result<T, E>>) -> Result<FoFormulaR<T>, E> {
+    Ok(match formula {
+        FoFormulaR::And(left, right) => FoFormulaR::And(left?, right?),
+        FoFormulaR::Or(left, right) => FoFormulaR::Or(left?, right?),
+        FoFormulaR::Not(inner) => FoFormulaR::Not(inner?),
+        FoFormulaR::Iff(left, right) => FoFormulaR::Iff(left?, right?),
+        FoFormulaR::Implies(left, right) => FoFormulaR::Implies(left?, right?),
+        FoFormulaR::Exists(var, inner) => FoFormulaR::Exists(var, inner?),
+        FoFormulaR::Forall(var, inner) => FoFormulaR::Forall(var, inner?),
+        FoFormulaR::PosLessEqual(var1, var2) => FoFormulaR::PosLessEqual(var1, var2),
+        FoFormulaR::PosLetter(var, letter) => FoFormulaR::PosLetter(var, letter),
+    })
 }
 
 /// TODO implement
-fn pullback_unrec(post_condition: FoFormulaR<FormulaS>, qf: &QfInterpretation) -> FormulaS {
-    match post_condition {
+fn pullback_unrec(
+    post_condition: FoFormulaR<FormulaS>,
+    qf: &FoInterpretation,
+) -> Result<FormulaS, PullbackError> {
+    Ok(match post_condition {
         FoFormulaR::And(left, right) => FormulaR {
             inside: FormulaF::And(Box::new(left), Box::new(right)),
         },
@@ -506,8 +769,8 @@ fn pullback_unrec(post_condition: FoFormulaR<FormulaS>, qf: &QfInterpretation) -
             let max_arity = qf.arities.iter().cloned().max().unwrap_or(0);
 
             let mut universe_formulas = Vec::new();
-            for (i, expr) in qf.universe.iter() {
-                let temp_formula_1 = universe_formula(qf, *i, &var);
+            for (i, _) in qf.universe.iter() {
+                let temp_formula_1 = universe_formula(qf, *i, &var)?;
                 let temp_formula_2 = FormulaR {
                     inside: FormulaF::Equal(
                         crate::two_sorted_formulas::Sort::Label,
@@ -528,9 +791,7 @@ fn pullback_unrec(post_condition: FoFormulaR<FormulaS>, qf: &QfInterpretation) -
             // let conjunction = FormulaF::And(inner, disjunction_univs);
             let conjuction = disjunction_univs.and(inner);
 
-            let final_form = quantify_exists(&var, max_arity, conjuction);
-
-            return final_form;
+            quantify_exists(&var, max_arity, conjuction)
         }
         FoFormulaR::Forall(var, inner) => {
             // TODO.
@@ -547,9 +808,8 @@ fn pullback_unrec(post_condition: FoFormulaR<FormulaS>, qf: &QfInterpretation) -
             let max_arity = qf.arities.iter().cloned().max().unwrap_or(0);
 
             let mut universe_formulas = Vec::new();
-            for (i, expr) in qf.universe.iter() {
-                let temp_formula_1 = universe_formula(qf, *i, &var);
-                eprintln!("temp_formula_1: {var} {:?}", temp_formula_1);
+            for (i, _) in qf.universe.iter() {
+                let temp_formula_1 = universe_formula(qf, *i, &var)?;
                 let temp_formula_2 = FormulaR {
                     inside: FormulaF::Equal(
                         crate::two_sorted_formulas::Sort::Label,
@@ -567,9 +827,7 @@ fn pullback_unrec(post_condition: FoFormulaR<FormulaS>, qf: &QfInterpretation) -
 
             let implication = disjunction_univs.implies(inner);
 
-            let final_form = quantify_forall(&var, max_arity, implication);
-
-            return final_form;
+            quantify_forall(&var, max_arity, implication)
         }
         FoFormulaR::PosLessEqual(var1, var2) => {
             // TODO.
@@ -583,15 +841,15 @@ fn pullback_unrec(post_condition: FoFormulaR<FormulaS>, qf: &QfInterpretation) -
             // ((l1 = lz /\ l2 = lp) /\ order_formula(z, p, l1, l2, qf))
             //
             let mut disjunctions = Vec::new();
-            for (label1_number, label1_string) in qf.labels.iter().enumerate() {
-                for (label2_number, label2_string) in qf.labels.iter().enumerate() {
+            for (label1_number, _) in qf.labels.iter().enumerate() {
+                for (label2_number, _) in qf.labels.iter().enumerate() {
                     let order_formula = order_formula(
                         qf,
                         label1_number,
                         label2_number,
                         var1.as_str(),
                         var2.as_str(),
-                    );
+                    )?;
                     let conjunction = FormulaR {
                         inside: FormulaF::And(
                             Box::new(FormulaR {
@@ -639,9 +897,9 @@ fn pullback_unrec(post_condition: FoFormulaR<FormulaS>, qf: &QfInterpretation) -
             // (lz = print3 /\ letter_formula(qf, print3, a, z))
             //
             let mut disjunctions = Vec::new();
-            for (index, label) in qf.labels.iter().enumerate() {
+            for (index, _) in qf.labels.iter().enumerate() {
                 if let Some(letter_formula) =
-                    letter_formula(qf, index, var.as_str(), letter.as_str())
+                    letter_formula(qf, index, var.as_str(), letter.as_str())?
                 {
                     let conjunction = FormulaR {
                         inside: FormulaF::Equal(
@@ -659,5 +917,5 @@ fn pullback_unrec(post_condition: FoFormulaR<FormulaS>, qf: &QfInterpretation) -
 
             disjunction(disjunctions)
         }
-    }
+    })
 }
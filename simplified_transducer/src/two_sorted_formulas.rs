@@ -140,6 +140,8 @@ pub enum FormulaF<F, A, S> {
     Equal(Sort, VarName, VarName),
     /// x <= y (of sort Position)
     LessEqual(VarName, VarName),
+    /// y = x + 1 (of sort Position)
+    Successor(VarName, VarName),
     /// a(x) (a in Σ, x : P)
     LetterAtPos(VarName, A),
     /// x = constant
@@ -184,6 +186,7 @@ where
             FormulaF::Not(inner) => format!("(not ({}))", inner),
             FormulaF::Equal(_, left, right) => format!("{} = {}", left, right),
             FormulaF::LessEqual(left, right) => format!("{} <= {}", left, right),
+            FormulaF::Successor(left, right) => format!("{} = {} + 1", right, left),
             FormulaF::LetterAtPos(var, letter) => {
                 format!("is_letter_{}({})", letter.to_alt_ergo(), var)
             }
@@ -217,8 +220,9 @@ where
             FormulaF::Not(inner) => format!("(not {}) ", inner),
             FormulaF::Equal(_, left, right) => format!("(= {} {}) ", left, right),
             FormulaF::LessEqual(left, right) => format!("(<= {} {}) ", left, right),
+            FormulaF::Successor(left, right) => format!("(= {} (+ {} 1)) ", right, left),
             FormulaF::LetterAtPos(var, letter) => {
-                format!("(= (word {}) {}) ", var, letter.to_smtlib())
+                format!("(= (word {}) {}) ", var, smtlib_letter(letter))
             }
             FormulaF::EqualConstant(var, value) => format!("(= {} {}) ", var, value.to_smtlib()),
         }
@@ -254,8 +258,9 @@ where
             FormulaF::Equal(Sort::Position, left, right) => format!("{} = {}", left, right),
             FormulaF::Equal(Sort::Label, left, right) => format!("{} in {}", left, right),
             FormulaF::LessEqual(left, right) => format!("{} <= {}", left, right),
+            FormulaF::Successor(left, right) => format!("{} = {} + 1", right, left),
             FormulaF::LetterAtPos(var, letter) => {
-                let letter = letter.to_smtlib();
+                let letter = letter.to_mona();
                 format!("{var} in L{letter}")
             }
             FormulaF::EqualConstant(var, value) => format!("{} in D{}", var, value.to_mona()),
@@ -308,6 +313,7 @@ where
             FormulaF::Equal(sort.clone(), left.clone(), right.clone())
         }
         FormulaF::LessEqual(left, right) => FormulaF::LessEqual(left.clone(), right.clone()),
+        FormulaF::Successor(left, right) => FormulaF::Successor(left.clone(), right.clone()),
         FormulaF::LetterAtPos(var, letter) => FormulaF::LetterAtPos(var.clone(), letter.clone()),
         FormulaF::EqualConstant(var, sort) => FormulaF::EqualConstant(var.clone(), sort.clone()),
     }
@@ -347,20 +353,56 @@ where
     }
 }
 
+/// Letters of the alphabets may be any character: SMT-LIB quotes the
+/// symbols that are not simple, MONA and Alt-Ergo only accept
+/// alphanumeric identifiers (which they prefix) so other characters
+/// are escaped by their code point
 impl ToSmtSolver for String {
     fn to_smtlib(&self) -> String {
-        self.clone()
+        let simple = self.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            && !self.starts_with(|c: char| c.is_ascii_digit());
+        if simple {
+            self.clone()
+        } else {
+            // quoted symbols cannot contain `|` nor `\\`
+            let quoted = self.replace('|', "_u7c_").replace('\\', "_u5c_");
+            format!("|{}|", quoted)
+        }
     }
 
     fn to_mona(&self) -> String {
-        self.clone()
+        escape_identifier(self)
     }
 
     fn to_alt_ergo(&self) -> String {
-        self.clone()
+        escape_identifier(self)
+    }
+}
+
+/// The constructor of a letter in SMT-LIB. Symbols like `open` or `len`
+/// may clash with the labels and the names of the preamble, so the
+/// letters longer than one character are prefixed.
+fn smtlib_letter<A: ToSmtSolver>(letter: &A) -> String {
+    let name = letter.to_smtlib();
+    match name.strip_prefix('|') {
+        Some(quoted) if quoted.chars().count() > 2 => format!("|letter_{}", quoted),
+        None if name.chars().count() > 1 => format!("letter_{}", name),
+        _ => name,
     }
 }
 
+fn escape_identifier(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                c.to_string()
+            } else {
+                format!("_u{:x}_", c as u32)
+            }
+        })
+        .collect()
+}
+
 impl<A, S> FormulaR<A, S> {
     pub fn and(self, other: FormulaR<A, S>) -> FormulaR<A, S> {
         FormulaR {
@@ -386,6 +428,7 @@ impl<A, S> FormulaR<A, S> {
         }
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn not(self) -> FormulaR<A, S> {
         FormulaR {
             inside: FormulaF::Not(Box::new(self)),
@@ -416,13 +459,20 @@ impl<A, S> FormulaR<A, S> {
         }
     }
 
+    /// `right = left + 1`
+    pub fn successor(left: VarName, right: VarName) -> FormulaR<A, S> {
+        FormulaR {
+            inside: FormulaF::Successor(left, right),
+        }
+    }
+
     pub fn letter_at_pos(var: VarName, letter: A) -> FormulaR<A, S> {
         FormulaR {
             inside: FormulaF::LetterAtPos(var, letter),
         }
     }
 
-    fn equal_constant(var: VarName, value: S) -> FormulaR<A, S> {
+    pub fn equal_constant(var: VarName, value: S) -> FormulaR<A, S> {
         FormulaR {
             inside: FormulaF::EqualConstant(var, value),
         }
@@ -487,36 +537,36 @@ where
         if i > 0 {
             buf.push_str(" | ");
         }
-        buf.push_str("L");
+        buf.push('L');
         buf.push_str(name.to_alt_ergo().as_str());
     }
-    buf.push_str("\n");
-    buf.push_str("\n");
+    buf.push('\n');
+    buf.push('\n');
     buf.push_str("(* encode a finite word *)\n");
     buf.push_str("logic len  : int\n");
     buf.push_str("logic word : int -> letter\n");
-    buf.push_str("\n");
+    buf.push('\n');
     buf.push_str("(* predicates for every letter in the word *)\n");
     for name in alphabet.iter() {
         buf.push_str("predicate is_letter_");
         buf.push_str(name.to_alt_ergo().as_str());
         buf.push_str("(i:int) = word(i) = L");
         buf.push_str(name.to_alt_ergo().as_str());
-        buf.push_str("\n");
+        buf.push('\n');
     }
-    buf.push_str("\n");
+    buf.push('\n');
     buf.push_str("(* the word has size len *)\n");
     buf.push_str("axiom word_is_finite: forall i:int.\n");
     buf.push_str("    ((0 <= i and i < len) -> word(i) <> E) and\n");
     buf.push_str("    (i >= len -> word(i) = E)  and\n");
     buf.push_str("    (i < 0    -> word(i) = E)\n");
-    buf.push_str("\n");
+    buf.push('\n');
     buf.push_str("(* non-empty length *)");
     buf.push_str("axiom word_is_non_empty: len > 0\n");
     buf.push_str("(* The final goal *)\n");
     buf.push_str("goal alt_ergo_goal: ");
     buf.push_str(formula.to_alt_ergo().as_str());
-    buf.push_str("\n");
+    buf.push('\n');
     buf
 }
 
@@ -551,22 +601,22 @@ where
     buf.push_str("(declare-datatype Letter ((blank) ");
     for (i, name) in alphabet.iter().enumerate() {
         if i > 0 {
-            buf.push_str(" ");
+            buf.push(' ');
         }
-        buf.push_str("(");
-        buf.push_str(name.to_smtlib().as_str());
-        buf.push_str(")");
+        buf.push('(');
+        buf.push_str(smtlib_letter(name).as_str());
+        buf.push(')');
     }
     buf.push_str("))\n");
     buf.push_str("; Declare the labels (for the print statements)\n");
     buf.push_str("(declare-datatype Label  (");
     for (i, name) in labels.iter().enumerate() {
         if i > 0 {
-            buf.push_str(" ");
+            buf.push(' ');
         }
-        buf.push_str("(");
+        buf.push('(');
         buf.push_str(name.to_smtlib().as_str());
-        buf.push_str(")");
+        buf.push(')');
     }
     buf.push_str("))\n");
     buf.push_str("(declare-const len Int)\n");
@@ -621,7 +671,7 @@ where
         if i > 0 {
             buf.push_str(", ");
         }
-        buf.push_str("L");
+        buf.push('L');
         buf.push_str(name.to_mona().as_str());
     }
     buf.push_str(";\n");
@@ -663,7 +713,7 @@ where
         if i > 0 {
             buf.push_str(" union ");
         }
-        buf.push_str("L");
+        buf.push('L');
         buf.push_str(name.to_mona().as_str());
     }
     buf.push_str(");\n");
@@ -674,7 +724,7 @@ where
         if i > 0 {
             buf.push_str(" inter ");
         }
-        buf.push_str("L");
+        buf.push('L');
         buf.push_str(name.to_mona().as_str());
     }
     buf.push_str(" = empty);\n");
@@ -846,7 +896,7 @@ impl SMTSolver {
         let output = std::process::Command::new(self.command())
             .arg(file_path)
             .output()
-            .expect(format!("failed to execute process {}", self.command()).as_str());
+            .unwrap_or_else(|e| panic!("failed to execute process {}: {:?}", self.command(), e));
 
         let utf8output = String::from_utf8(output.stdout).unwrap();
 
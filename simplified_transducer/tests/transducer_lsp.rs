@@ -0,0 +1,141 @@
+use serde_json::{json, Value};
+use simplified_transducer::lsp::{read_message, write_message};
+use std::io::BufReader;
+use std::process::{Command, Stdio};
+
+// Send the messages to the server and collect its answers
+fn session(messages: &[Value]) -> Vec<Value> {
+    let mut server = Command::new(env!("CARGO_BIN_EXE_transducer-lsp"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Unable to start the server");
+    let mut stdin = server.stdin.take().unwrap();
+    for message in messages {
+        write_message(&mut stdin, message).unwrap();
+    }
+    drop(stdin);
+    let mut stdout = BufReader::new(server.stdout.take().unwrap());
+    let mut answers = Vec::new();
+    while let Some(answer) = read_message(&mut stdout).unwrap() {
+        answers.push(answer);
+    }
+    assert!(server.wait().unwrap().success());
+    answers
+}
+
+fn request(id: i32, method: &str, params: Value) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params})
+}
+
+fn notification(method: &str, params: Value) -> Value {
+    json!({"jsonrpc": "2.0", "method": method, "params": params})
+}
+
+fn at(uri: &str, line: usize, character: usize) -> Value {
+    json!({"textDocument": {"uri": uri}, "position": {"line": line, "character": character}})
+}
+
+#[test]
+fn test_transducer_lsp() {
+    let uri = "file:///example.txt";
+    let text = "for i in 0..n {\n  print(i.label)\n  for j in 0..2 {\n    print(\"a\" + j.label)\n  }\n}\n";
+    let answers = session(&[
+        request(1, "initialize", json!({"capabilities": {}})),
+        notification("initialized", json!({})),
+        notification(
+            "textDocument/didOpen",
+            json!({"textDocument": {"uri": uri, "languageId": "transducer", "version": 1, "text": text}}),
+        ),
+        request(2, "textDocument/hover", at(uri, 1, 3)),
+        request(3, "textDocument/hover", at(uri, 3, 5)),
+        request(4, "textDocument/definition", at(uri, 3, 18)),
+        request(
+            5,
+            "textDocument/formatting",
+            json!({"textDocument": {"uri": uri}}),
+        ),
+        notification(
+            "textDocument/didChange",
+            json!({"textDocument": {"uri": uri, "version": 2}, "contentChanges": [{"text": "for i in 0..n {\n  print(k.label)\n}\n"}]}),
+        ),
+        request(6, "shutdown", Value::Null),
+        notification("exit", Value::Null),
+    ]);
+    assert_eq!(answers.len(), 8);
+    assert_eq!(answers[0]["result"]["capabilities"]["hoverProvider"], true);
+    assert_eq!(answers[1]["params"]["diagnostics"], json!([]));
+
+    // the outputs of a `print` of the unrolled loop, one per copy, the
+    // copy on the second position being in the loop on the first one
+    assert_eq!(
+        answers[2]["result"]["contents"]["value"],
+        "- label `[0, 0]`, arity 1"
+    );
+    assert_eq!(
+        answers[3]["result"]["contents"]["value"],
+        "- label `[0, 1, 0, 0]`, arity 2\n- label `[0, 1, 0, 1]`, arity 2\n\
+         - label `[0, 1, 0, 2, 0, 0]`, arity 3\n- label `[0, 1, 0, 2, 0, 1]`, arity 3"
+    );
+
+    // `j.label` goes to `for j`
+    assert_eq!(
+        answers[4]["result"]["range"],
+        json!({"start": {"line": 2, "character": 6}, "end": {"line": 2, "character": 7}})
+    );
+    let formatted = answers[5]["result"][0]["newText"].as_str().unwrap();
+    assert!(formatted.starts_with("for i in 0..n {\n    print(i.label)\n    for j in 0..2 {\n"));
+
+    let diagnostics = &answers[6]["params"]["diagnostics"];
+    assert_eq!(diagnostics.as_array().unwrap().len(), 1);
+    assert_eq!(
+        diagnostics[0]["range"],
+        json!({"start": {"line": 1, "character": 8}, "end": {"line": 1, "character": 15}})
+    );
+    assert!(diagnostics[0]["message"]
+        .as_str()
+        .unwrap()
+        .starts_with("`k` is not bound by an enclosing loop"));
+    assert_eq!(
+        answers[7],
+        json!({"jsonrpc": "2.0", "id": 6, "result": null})
+    );
+}
+
+#[test]
+fn test_transducer_lsp_scopes() {
+    let uri = "file:///scopes.txt";
+    let text = "proc show(k) {\n  print(k.label)\n}\nfor i in 0..n { show(i) }\nfor i in n..0 { print(i.label) }\n";
+    let answers = session(&[
+        request(1, "initialize", json!({"capabilities": {}})),
+        notification(
+            "textDocument/didOpen",
+            json!({"textDocument": {"uri": uri, "languageId": "transducer", "version": 1, "text": text}}),
+        ),
+        request(2, "textDocument/definition", at(uri, 3, 21)),
+        request(3, "textDocument/definition", at(uri, 4, 22)),
+        request(4, "textDocument/hover", at(uri, 1, 3)),
+        request(5, "textDocument/hover", at(uri, 4, 17)),
+    ]);
+    assert_eq!(answers.len(), 6);
+
+    // each `i` goes to the loop it is in
+    assert_eq!(
+        answers[2]["result"]["range"],
+        json!({"start": {"line": 3, "character": 4}, "end": {"line": 3, "character": 5}})
+    );
+    assert_eq!(
+        answers[3]["result"]["range"],
+        json!({"start": {"line": 4, "character": 4}, "end": {"line": 4, "character": 5}})
+    );
+
+    // the print of a procedure has the outputs of its calls
+    assert_eq!(
+        answers[4]["result"]["contents"]["value"],
+        "- label `[0, 0]`, arity 1"
+    );
+    assert_eq!(
+        answers[5]["result"]["contents"]["value"],
+        "- label `[1, 0]`, arity 1"
+    );
+}
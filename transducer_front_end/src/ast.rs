@@ -0,0 +1,415 @@
+use crate::diagnostic::Span;
+use std::collections::HashMap;
+use std::fmt;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// A program with the letters it reads and the letters it prints
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub alphabet: Alphabet,
+    pub stmts: Vec<Stmt>,
+}
+
+/// The input and output alphabets, declared by the `alphabet` header.
+/// A letter is a symbol of one or more characters, like `a` or `open`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alphabet {
+    pub input: Vec<String>,
+    pub output: Vec<String>,
+    pub mode: InputMode,
+}
+
+impl Default for Alphabet {
+    // The letters of the programs without an `alphabet` header
+    fn default() -> Self {
+        let letters: Vec<String> = ["a", "b", "#"].iter().map(|l| l.to_string()).collect();
+        Alphabet {
+            input: letters.clone(),
+            output: letters,
+            mode: InputMode::default(),
+        }
+    }
+}
+
+impl Alphabet {
+    /// Split an input word into letters. The letters are separated by
+    /// whitespace or commas (`open, sep close`), and a piece that is not a
+    /// letter is cut into the symbols of the input mode, so that the words
+    /// over one-symbol letters are written without separators (`ab#a`).
+    /// The error is the first piece that is not made of letters.
+    pub fn split_word(&self, word: &str) -> Result<Vec<String>, String> {
+        let mut letters = Vec::new();
+        let pieces = word.split(|c: char| c.is_whitespace() || c == ',');
+        for piece in pieces.filter(|piece| !piece.is_empty()) {
+            if self.input.iter().any(|letter| letter == piece) {
+                letters.push(piece.to_string());
+                continue;
+            }
+            for symbol in self.mode.split(piece) {
+                if !self.input.contains(&symbol) {
+                    // the unknown symbol, when the letters are symbols
+                    let words = self.input.iter().any(|letter| !self.mode.is_symbol(letter));
+                    return Err(if words { piece.to_string() } else { symbol });
+                }
+                letters.push(symbol);
+            }
+        }
+        Ok(letters)
+    }
+
+    /// The word made of output letters, see `InputMode::join_word`
+    pub fn join_word(&self, letters: &[String]) -> String {
+        self.mode.join_word(letters)
+    }
+}
+
+/// How strings are cut into symbols: the strings of letters of the
+/// `alphabet` header and of the prints, and the input words. It is
+/// declared by a `mode bytes` section of the header.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum InputMode {
+    /// Every byte is a symbol, the bytes that are not ASCII being written
+    /// as escapes like `\xc3`
+    Bytes,
+    /// Every Unicode scalar value is a symbol
+    #[default]
+    Scalars,
+    /// Every extended grapheme cluster, like `e` and a combining accent,
+    /// is a symbol
+    Graphemes,
+}
+
+impl InputMode {
+    pub const NAMES: [&'static str; 3] = ["bytes", "scalars", "graphemes"];
+
+    pub fn from_name(name: &str) -> Option<InputMode> {
+        match name {
+            "bytes" => Some(InputMode::Bytes),
+            "scalars" => Some(InputMode::Scalars),
+            "graphemes" => Some(InputMode::Graphemes),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            InputMode::Bytes => "bytes",
+            InputMode::Scalars => "scalars",
+            InputMode::Graphemes => "graphemes",
+        }
+    }
+
+    /// The symbols of a string
+    pub fn split(self, s: &str) -> Vec<String> {
+        match self {
+            InputMode::Bytes => s
+                .bytes()
+                .map(|b| {
+                    if b.is_ascii() {
+                        char::from(b).to_string()
+                    } else {
+                        format!("\\x{:02x}", b)
+                    }
+                })
+                .collect(),
+            InputMode::Scalars => s.chars().map(|c| c.to_string()).collect(),
+            InputMode::Graphemes => s.graphemes(true).map(|g| g.to_string()).collect(),
+        }
+    }
+
+    /// Whether a letter is a single symbol
+    pub fn is_symbol(self, letter: &str) -> bool {
+        match self {
+            InputMode::Bytes => byte(letter).is_some(),
+            _ => self.split(letter).len() == 1,
+        }
+    }
+
+    /// The word made of letters: they are concatenated when they are all
+    /// symbols, the bytes being decoded, and separated by spaces otherwise
+    pub fn join_word(self, letters: &[String]) -> String {
+        if !letters.iter().all(|letter| self.is_symbol(letter)) {
+            return letters.join(" ");
+        }
+        match self {
+            InputMode::Bytes => {
+                let bytes: Vec<u8> = letters.iter().filter_map(|letter| byte(letter)).collect();
+                String::from_utf8_lossy(&bytes).into_owned()
+            }
+            _ => letters.concat(),
+        }
+    }
+}
+
+// The byte written by a letter of the bytes mode, an ASCII character or
+// an escape like `\xc3`
+fn byte(letter: &str) -> Option<u8> {
+    match letter.strip_prefix("\\x") {
+        Some(hex) if hex.len() == 2 => u8::from_str_radix(hex, 16).ok().filter(|b| !b.is_ascii()),
+        _ if letter.len() == 1 => Some(letter.as_bytes()[0]),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Stmt {
+    Print(Pexpr, Span),      // printed letter, and the print statement it comes from
+    For0(String, Vec<Stmt>), //first to last
+    For1(String, Vec<Stmt>), //last to first
+    If(Bexpr, Vec<Stmt>),    // If statement with condition, then branch
+    Declare(String, Bexpr),  // var b := condition
+    Assign(String, Bexpr),   // b := condition
+    Break,                   // exits the innermost loop
+    // Positions at a constant distance from an end of the word, `k` or
+    // `n - k`, in the order they are visited; those outside the word are
+    // skipped
+    ForConst(String, Vec<Bexpr>, Vec<Stmt>),
+    Repeat(usize, Vec<Stmt>), // body run a fixed number of times
+}
+
+/// Most copies of a body made by unrolling the constant loops and the
+/// repetitions of a program. The copy of a loop at distance `k` from an
+/// end of the word is nested in `k` loops, so this also bounds the number
+/// of positions of its outputs.
+pub const MAX_UNROLLED: usize = 16;
+
+#[derive(Debug, Clone)]
+pub enum Pexpr {
+    Label(String),
+    Str(String),
+    Map(Vec<(String, String)>, String), // Label through a letter substitution
+}
+
+#[derive(Debug, Clone)]
+pub enum Bexpr {
+    Var(String),
+    Str(String),
+    LessEqual(Box<Bexpr>, Box<Bexpr>), // Less than or equal to comparison
+    Less(Box<Bexpr>, Box<Bexpr>),      // Less than comparison
+    Equal(Box<Bexpr>, Box<Bexpr>),     // Equal to comparison
+    NotEqual(Box<Bexpr>, Box<Bexpr>),  // Not equal to comparison
+    GreaterEqual(Box<Bexpr>, Box<Bexpr>), // Greater than or equal to comparison
+    Greater(Box<Bexpr>, Box<Bexpr>),   // Greater than comparison
+    Not(Box<Bexpr>),
+    Label(String),
+    And(Box<Bexpr>, Box<Bexpr>),
+    Or(Box<Bexpr>, Box<Bexpr>),
+    Offset(String, i32),          // Position variable (or `n`) plus a constant
+    Number(i32),                  // Integer constant
+    LetterIn(String, Vec<String>), // The label of a position is one of the letters
+    Flag(String),                 // Boolean variable
+    Exists(String, Box<Bexpr>),   // Quantification over positions
+    Forall(String, Box<Bexpr>),
+    True,
+    False,
+}
+
+impl Bexpr {
+    // The negation of a condition. Comparisons are flipped and the
+    // negation is pushed through `&&` and `||` (De Morgan), any other
+    // expression is wrapped in `Not`.
+    pub fn negated(&self) -> Bexpr {
+        match self {
+            Bexpr::Equal(left, right) => Bexpr::NotEqual(left.clone(), right.clone()),
+            Bexpr::NotEqual(left, right) => Bexpr::Equal(left.clone(), right.clone()),
+            Bexpr::Less(left, right) => Bexpr::GreaterEqual(left.clone(), right.clone()),
+            Bexpr::GreaterEqual(left, right) => Bexpr::Less(left.clone(), right.clone()),
+            Bexpr::Greater(left, right) => Bexpr::LessEqual(left.clone(), right.clone()),
+            Bexpr::LessEqual(left, right) => Bexpr::Greater(left.clone(), right.clone()),
+            Bexpr::And(left, right) => {
+                Bexpr::Or(Box::new(left.negated()), Box::new(right.negated()))
+            }
+            Bexpr::Or(left, right) => {
+                Bexpr::And(Box::new(left.negated()), Box::new(right.negated()))
+            }
+            Bexpr::Not(inner) => (**inner).clone(),
+            Bexpr::Exists(var, inner) => Bexpr::Forall(var.clone(), Box::new(inner.negated())),
+            Bexpr::Forall(var, inner) => Bexpr::Exists(var.clone(), Box::new(inner.negated())),
+            Bexpr::True => Bexpr::False,
+            Bexpr::False => Bexpr::True,
+            _ => Bexpr::Not(Box::new(self.clone())),
+        }
+    }
+
+    // Whether the condition reads a boolean variable
+    pub fn contains_flag(&self) -> bool {
+        match self {
+            Bexpr::Flag(_) => true,
+            Bexpr::LessEqual(left, right)
+            | Bexpr::Less(left, right)
+            | Bexpr::Equal(left, right)
+            | Bexpr::NotEqual(left, right)
+            | Bexpr::GreaterEqual(left, right)
+            | Bexpr::Greater(left, right)
+            | Bexpr::And(left, right)
+            | Bexpr::Or(left, right) => left.contains_flag() || right.contains_flag(),
+            Bexpr::Not(inner) | Bexpr::Exists(_, inner) | Bexpr::Forall(_, inner) => {
+                inner.contains_flag()
+            }
+            Bexpr::Var(_)
+            | Bexpr::Str(_)
+            | Bexpr::Label(_)
+            | Bexpr::Offset(_, _)
+            | Bexpr::Number(_)
+            | Bexpr::LetterIn(_, _)
+            | Bexpr::True
+            | Bexpr::False => false,
+        }
+    }
+}
+
+/// Whether the statements may break out of the loop around them
+pub fn breaks_loop(stmts: &[Stmt]) -> bool {
+    stmts.iter().any(|stmt| match stmt {
+        Stmt::Break => true,
+        // a repetition is not a loop, its breaks exit the loop around it
+        Stmt::If(_, body) | Stmt::Repeat(_, body) => breaks_loop(body),
+        _ => false,
+    })
+}
+
+/// The loop variables and the boolean variables declared in the statements
+pub fn collect_bound(stmts: &[Stmt], bound: &mut Vec<String>) {
+    for stmt in stmts {
+        match stmt {
+            Stmt::For0(var, body) | Stmt::For1(var, body) | Stmt::ForConst(var, _, body) => {
+                bound.push(var.clone());
+                collect_bound(body, bound);
+            }
+            Stmt::If(_, body) | Stmt::Repeat(_, body) => collect_bound(body, bound),
+            Stmt::Declare(flag, _) => bound.push(flag.clone()),
+            Stmt::Print(_, _) | Stmt::Assign(_, _) | Stmt::Break => {}
+        }
+    }
+}
+
+/// Rename the variables of a statement, the variables missing from
+/// `renaming` being kept
+pub fn rename_stmt(stmt: &Stmt, renaming: &HashMap<String, String>) -> Stmt {
+    let rename = |name: &String| renaming.get(name).unwrap_or(name).clone();
+    let rename_body = |body: &[Stmt]| body.iter().map(|s| rename_stmt(s, renaming)).collect();
+    match stmt {
+        Stmt::Print(Pexpr::Label(var), span) => Stmt::Print(Pexpr::Label(rename(var)), *span),
+        Stmt::Print(Pexpr::Map(table, var), span) => {
+            Stmt::Print(Pexpr::Map(table.clone(), rename(var)), *span)
+        }
+        Stmt::Print(Pexpr::Str(s), span) => Stmt::Print(Pexpr::Str(s.clone()), *span),
+        Stmt::For0(var, body) => Stmt::For0(rename(var), rename_body(body)),
+        Stmt::For1(var, body) => Stmt::For1(rename(var), rename_body(body)),
+        Stmt::ForConst(var, positions, body) => {
+            Stmt::ForConst(rename(var), positions.clone(), rename_body(body))
+        }
+        Stmt::Repeat(count, body) => Stmt::Repeat(*count, rename_body(body)),
+        Stmt::If(condition, body) => {
+            Stmt::If(remap_bexpr_with_map(condition, renaming), rename_body(body))
+        }
+        Stmt::Declare(flag, value) => {
+            Stmt::Declare(rename(flag), remap_bexpr_with_map(value, renaming))
+        }
+        Stmt::Assign(flag, value) => {
+            Stmt::Assign(rename(flag), remap_bexpr_with_map(value, renaming))
+        }
+        Stmt::Break => Stmt::Break,
+    }
+}
+
+/// Rename the variables of a condition, the variables missing from `map`
+/// being kept
+pub fn remap_bexpr_with_map(expr: &Bexpr, map: &HashMap<String, String>) -> Bexpr {
+    match expr {
+        Bexpr::Var(var) => Bexpr::Var(map.get(var).cloned().unwrap_or_else(|| var.clone())),
+        Bexpr::Str(s) => Bexpr::Str(s.clone()),
+        Bexpr::Less(lhs, rhs) => Bexpr::Less(
+            Box::new(remap_bexpr_with_map(lhs, map)),
+            Box::new(remap_bexpr_with_map(rhs, map)),
+        ),
+        Bexpr::LessEqual(lhs, rhs) => Bexpr::LessEqual(
+            Box::new(remap_bexpr_with_map(lhs, map)),
+            Box::new(remap_bexpr_with_map(rhs, map)),
+        ),
+        Bexpr::Equal(lhs, rhs) => Bexpr::Equal(
+            Box::new(remap_bexpr_with_map(lhs, map)),
+            Box::new(remap_bexpr_with_map(rhs, map)),
+        ),
+        Bexpr::NotEqual(lhs, rhs) => Bexpr::NotEqual(
+            Box::new(remap_bexpr_with_map(lhs, map)),
+            Box::new(remap_bexpr_with_map(rhs, map)),
+        ),
+        Bexpr::GreaterEqual(lhs, rhs) => Bexpr::GreaterEqual(
+            Box::new(remap_bexpr_with_map(lhs, map)),
+            Box::new(remap_bexpr_with_map(rhs, map)),
+        ),
+        Bexpr::Greater(lhs, rhs) => Bexpr::Greater(
+            Box::new(remap_bexpr_with_map(lhs, map)),
+            Box::new(remap_bexpr_with_map(rhs, map)),
+        ),
+        Bexpr::Not(expr) => Bexpr::Not(Box::new(remap_bexpr_with_map(expr, map))),
+        Bexpr::And(lhs, rhs) => Bexpr::And(
+            Box::new(remap_bexpr_with_map(lhs, map)),
+            Box::new(remap_bexpr_with_map(rhs, map)),
+        ),
+        Bexpr::Or(lhs, rhs) => Bexpr::Or(
+            Box::new(remap_bexpr_with_map(lhs, map)),
+            Box::new(remap_bexpr_with_map(rhs, map)),
+        ),
+        Bexpr::Label(label) => {
+            Bexpr::Label(map.get(label).cloned().unwrap_or_else(|| label.clone()))
+        }
+        Bexpr::Offset(var, offset) => Bexpr::Offset(
+            map.get(var).cloned().unwrap_or_else(|| var.clone()),
+            *offset,
+        ),
+        Bexpr::Number(n) => Bexpr::Number(*n),
+        Bexpr::LetterIn(label, letters) => Bexpr::LetterIn(
+            map.get(label).cloned().unwrap_or_else(|| label.clone()),
+            letters.clone(),
+        ),
+        Bexpr::Flag(flag) => Bexpr::Flag(map.get(flag).cloned().unwrap_or_else(|| flag.clone())),
+        Bexpr::Exists(var, expr) => {
+            Bexpr::Exists(var.clone(), Box::new(remap_bexpr_with_map(expr, map)))
+        }
+        Bexpr::Forall(var, expr) => {
+            Bexpr::Forall(var.clone(), Box::new(remap_bexpr_with_map(expr, map)))
+        }
+        Bexpr::True => Bexpr::True,
+        Bexpr::False => Bexpr::False,
+    }
+}
+
+impl fmt::Display for Bexpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Bexpr::Var(var) => write!(f, "{}", var),
+            Bexpr::Str(s) => write!(f, "\"{}\"", s),
+            Bexpr::Less(lhs, rhs) => write!(f, "({} < {})", lhs, rhs),
+            Bexpr::LessEqual(lhs, rhs) => write!(f, "({} <= {})", lhs, rhs),
+            Bexpr::Equal(lhs, rhs) => write!(f, "({} == {})", lhs, rhs),
+            Bexpr::NotEqual(lhs, rhs) => write!(f, "({} != {})", lhs, rhs),
+            Bexpr::GreaterEqual(lhs, rhs) => write!(f, "({} >= {})", lhs, rhs),
+            Bexpr::Greater(lhs, rhs) => write!(f, "({} > {})", lhs, rhs),
+            Bexpr::Not(expr) => write!(f, "!( {} )", expr),
+            Bexpr::And(lhs, rhs) => write!(f, "( {} && {} )", lhs, rhs),
+            Bexpr::Or(lhs, rhs) => write!(f, "( {} || {} )", lhs, rhs),
+            Bexpr::Label(label) => write!(f, "{}", label),
+            Bexpr::Offset(var, offset) if *offset < 0 => write!(f, "({} - {})", var, -offset),
+            Bexpr::Offset(var, offset) => write!(f, "({} + {})", var, offset),
+            Bexpr::Number(n) => write!(f, "{}", n),
+            Bexpr::LetterIn(label, letters) => {
+                let letters: Vec<String> = letters.iter().map(|l| format!("\"{}\"", l)).collect();
+                write!(f, "({} in {{{}}})", label, letters.join(", "))
+            }
+            Bexpr::Flag(flag) => write!(f, "{}", flag),
+            Bexpr::Exists(var, expr) => write!(f, "(exists {}. {})", var, expr),
+            Bexpr::Forall(var, expr) => write!(f, "(forall {}. {})", var, expr),
+            Bexpr::True => write!(f, "T"),
+            Bexpr::False => write!(f, "F"),
+        }
+    }
+}
+
+pub enum Fexpr {
+    Number(i32),
+    Str(String),
+    Var(String),
+    Label(String),
+}
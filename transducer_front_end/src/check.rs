@@ -0,0 +1,459 @@
+///
+/// Static checks of a program, run between the parsing and the lowering.
+/// Every variable is resolved to the loop, quantifier, parameter or
+/// declaration binding it in the scopes of the source, and every
+/// expression gets a sort: a position, a letter, a string or a boolean.
+/// Unbound variables, variables shadowing the variable of an enclosing
+/// loop and ill-sorted comparisons are reported at their place in the
+/// source, instead of panicking in the interpreter or the pullback.
+///
+use crate::diagnostic::{Diagnostic, Span};
+use crate::lexer::{Syntax, LENGTH_VARIABLE};
+use crate::surface::{Expr, ExprKind, Name, Printed, Range, Source, Statement, StatementKind};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// The place where each variable is bound, by the place of its uses
+pub type Bindings = HashMap<Span, Span>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Sort {
+    Position,
+    Letter,
+    String,
+    Boolean,
+}
+
+impl fmt::Display for Sort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Sort::Position => write!(f, "a position"),
+            Sort::Letter => write!(f, "a letter"),
+            Sort::String => write!(f, "a string"),
+            Sort::Boolean => write!(f, "a boolean"),
+        }
+    }
+}
+
+// What a variable is bound to: a position, a block (represented by its
+// first position) or a boolean
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Position,
+    Block,
+    Flag,
+}
+
+/// Check the statements of a source, giving the binding of every use of
+/// a variable and the errors found
+pub fn check(source: &Source) -> (Bindings, Vec<Diagnostic>) {
+    let mut checker = Checker {
+        syntax: source.syntax,
+        scopes: vec![vec![]],
+        bindings: HashMap::new(),
+        unbound: HashSet::new(),
+        diagnostics: Vec::new(),
+    };
+    checker.block(&source.statements);
+    (checker.bindings, checker.diagnostics)
+}
+
+struct Checker {
+    syntax: Syntax,
+    // the variables bound in each enclosing block, with their binding
+    scopes: Vec<Vec<(String, Span, Kind)>>,
+    bindings: Bindings,
+    // the unbound variables already reported
+    unbound: HashSet<String>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Checker {
+    fn block(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    // The statements of a block, which has its own scope
+    fn scoped(&mut self, statements: &[Statement]) {
+        self.scopes.push(vec![]);
+        self.block(statements);
+        self.scopes.pop();
+    }
+
+    // The statements of a procedure or a transducer, which only see their
+    // own variables
+    fn isolated(&mut self, parameters: &[Name], statements: &[Statement]) {
+        let outer = std::mem::replace(&mut self.scopes, vec![vec![]]);
+        for parameter in parameters {
+            self.bind(parameter, Kind::Position);
+        }
+        self.block(statements);
+        self.scopes = outer;
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match &statement.kind {
+            StatementKind::Print(printed) => {
+                for term in printed {
+                    if let Printed::Label(var) | Printed::Map(_, var) = term {
+                        self.position(var);
+                    }
+                }
+            }
+            StatementKind::For(var, range, body) => {
+                let kind = match range {
+                    Range::Positions { start, end, .. } => {
+                        self.bound(start);
+                        self.bound(end);
+                        Kind::Position
+                    }
+                    Range::Block { block, .. } => {
+                        self.block_of(block);
+                        Kind::Position
+                    }
+                    Range::Blocks { .. } => Kind::Block,
+                    Range::Stdin { .. } => Kind::Position,
+                };
+                self.scopes.push(vec![]);
+                self.bind(var, kind);
+                self.block(body);
+                self.scopes.pop();
+            }
+            StatementKind::Repeat(_, _, body) => self.scoped(body),
+            StatementKind::If(branches, otherwise) => {
+                for (condition, body) in branches {
+                    self.condition(condition);
+                    self.scoped(body);
+                }
+                if let Some(body) = otherwise {
+                    self.scoped(body);
+                }
+            }
+            StatementKind::Declare(flag, value) => {
+                self.condition(value);
+                self.bind(flag, Kind::Flag);
+            }
+            StatementKind::Initialize(flag) => self.bind(flag, Kind::Flag),
+            StatementKind::Assign(flag, value) => {
+                self.assigned(flag);
+                self.condition(value);
+            }
+            StatementKind::Proc(_, parameters, body) => self.isolated(parameters, body),
+            StatementKind::Call(_, arguments) => {
+                for argument in arguments {
+                    self.argument(argument);
+                }
+            }
+            StatementKind::Transducer(_, _, body) => self.isolated(&[], body),
+            StatementKind::Break
+            | StatementKind::Skip
+            | StatementKind::Class(_, _)
+            | StatementKind::Map(_, _)
+            | StatementKind::Main(_) => {}
+        }
+    }
+
+    // The binding of a variable in the enclosing scopes
+    fn lookup(&self, name: &str) -> Option<(Span, Kind)> {
+        (self.scopes.iter().rev())
+            .flat_map(|scope| scope.iter().rev())
+            .find(|(bound, _, _)| bound == name)
+            .map(|(_, span, kind)| (*span, *kind))
+    }
+
+    // Enter the scope of a variable. A loop or quantifier variable may
+    // not shadow the variable of an enclosing loop.
+    fn bind(&mut self, var: &Name, kind: Kind) {
+        let shadowed = matches!(
+            self.lookup(&var.name),
+            Some((_, Kind::Position | Kind::Block))
+        );
+        if kind != Kind::Flag && shadowed {
+            self.diagnostics.push(
+                Diagnostic::error(
+                    format!("`{}` shadows the variable of an enclosing loop", var.name),
+                    var.span,
+                )
+                .with_help("give the inner variable another name"),
+            );
+        }
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push((var.name.clone(), var.span, kind));
+        }
+    }
+
+    // Resolve a use of a variable. An unbound variable is reported once.
+    fn resolve(&mut self, var: &Name) -> Option<Kind> {
+        if var.name == LENGTH_VARIABLE {
+            return Some(Kind::Position);
+        }
+        match self.lookup(&var.name) {
+            Some((binding, kind)) => {
+                self.bindings.insert(var.span, binding);
+                Some(kind)
+            }
+            None => {
+                if self.unbound.insert(var.name.clone()) {
+                    self.diagnostics.push(
+                        Diagnostic::error(
+                            format!("`{}` is not bound by an enclosing loop", var.name),
+                            var.span,
+                        )
+                        .with_help(format!("declare it with `for {} in 0..n`", var.name)),
+                    );
+                }
+                None
+            }
+        }
+    }
+
+    // A use of a position variable, whether it is one
+    fn position(&mut self, var: &Name) -> bool {
+        match self.resolve(var) {
+            Some(Kind::Position | Kind::Block) => true,
+            Some(Kind::Flag) => {
+                self.diagnostics.push(Diagnostic::error(
+                    format!("`{}` is a boolean, not a position", var.name),
+                    var.span,
+                ));
+                false
+            }
+            None => false,
+        }
+    }
+
+    // A position variable given to a procedure or to `first` and `last`,
+    // which `n` is not
+    fn argument(&mut self, var: &Name) {
+        if var.name == LENGTH_VARIABLE {
+            self.diagnostics.push(Diagnostic::error(
+                format!("expected position variable, found `{}`", var.name),
+                var.span,
+            ));
+        } else {
+            self.position(var);
+        }
+    }
+
+    // The block whose positions a loop visits
+    fn block_of(&mut self, block: &Name) {
+        if let Some(Kind::Position | Kind::Flag) = self.resolve(block) {
+            self.diagnostics.push(
+                Diagnostic::error(format!("`{}` is not a block", block.name), block.span)
+                    .with_help("the blocks are bound by `for b in blocks(stdin, \"#\")`"),
+            );
+        }
+    }
+
+    // The boolean variable of an assignment
+    fn assigned(&mut self, flag: &Name) {
+        match self.lookup(&flag.name) {
+            Some((binding, Kind::Flag)) => {
+                self.bindings.insert(flag.span, binding);
+            }
+            _ => {
+                let declaration = match self.syntax {
+                    Syntax::Braces => format!("var {} := False", flag.name),
+                    Syntax::Paper => format!("initialize {}", flag.name),
+                };
+                self.diagnostics.push(
+                    Diagnostic::error(format!("cannot assign to `{}`", flag.name), flag.span)
+                        .with_help(format!("declare a boolean variable with `{}`", declaration)),
+                );
+            }
+        }
+    }
+
+    // A bound of a range, which is a position
+    fn bound(&mut self, expr: &Expr) {
+        if let Some(sort) = self.sort(expr) {
+            if sort != Sort::Position {
+                self.diagnostics.push(Diagnostic::error(
+                    format!("expected a position, found {}", sort),
+                    expr.span,
+                ));
+            }
+        }
+    }
+
+    fn condition(&mut self, expr: &Expr) {
+        if let Some(sort) = self.sort(expr) {
+            if sort != Sort::Boolean {
+                self.diagnostics.push(Diagnostic::error(
+                    format!("expected a condition, found {}", sort),
+                    expr.span,
+                ));
+            }
+        }
+    }
+
+    // The sort of an expression, or `None` when an error was reported in it
+    fn sort(&mut self, expr: &Expr) -> Option<Sort> {
+        match &expr.kind {
+            ExprKind::True | ExprKind::False => Some(Sort::Boolean),
+            ExprKind::Number(_) => Some(Sort::Position),
+            ExprKind::Str(_) => Some(Sort::String),
+            ExprKind::Var(var) => match self.resolve(var)? {
+                Kind::Position | Kind::Block => Some(Sort::Position),
+                Kind::Flag => Some(Sort::Boolean),
+            },
+            ExprKind::Offset(var, _) => self.position(var).then_some(Sort::Position),
+            ExprKind::Label(var) => self.position(var).then_some(Sort::Letter),
+            ExprKind::First(var) | ExprKind::Last(var) => {
+                self.argument(var);
+                Some(Sort::Boolean)
+            }
+            ExprKind::In(var, _) => {
+                self.position(var);
+                Some(Sort::Boolean)
+            }
+            ExprKind::Not(inner) => {
+                self.condition(inner);
+                Some(Sort::Boolean)
+            }
+            ExprKind::And(left, right) | ExprKind::Or(left, right) => {
+                self.condition(left);
+                self.condition(right);
+                Some(Sort::Boolean)
+            }
+            ExprKind::Exists(var, inner) | ExprKind::Forall(var, inner) => {
+                self.scopes.push(vec![]);
+                self.bind(var, Kind::Position);
+                self.condition(inner);
+                self.scopes.pop();
+                Some(Sort::Boolean)
+            }
+            ExprKind::Paren(inner) => self.sort(inner),
+            ExprKind::Compare(left, comparison, operator, right) => {
+                let (left, right) = (self.sort(left), self.sort(right));
+                let sorts = (left?, right?);
+                let equality = comparison.is_equality();
+                // booleans are compared with `==` and `!=`, with booleans
+                let diagnostic = match sorts {
+                    (Sort::Boolean, Sort::Boolean) if equality => None,
+                    (Sort::Boolean, Sort::Boolean) => Some(
+                        Diagnostic::error(
+                            format!("booleans cannot be compared with `{}`", comparison),
+                            *operator,
+                        )
+                        .with_help("use `==` or `!=`"),
+                    ),
+                    (Sort::Boolean, _) | (_, Sort::Boolean) => Some(Diagnostic::error(
+                        "cannot compare a boolean with a position or a letter",
+                        *operator,
+                    )),
+                    (Sort::Position, Sort::Position) => None,
+                    (Sort::Letter, Sort::Letter | Sort::String) | (Sort::String, Sort::Letter)
+                        if equality =>
+                    {
+                        None
+                    }
+                    _ if equality => Some(Diagnostic::error(
+                        format!("cannot compare {} with {}", sorts.0, sorts.1),
+                        expr.span,
+                    )),
+                    _ => Some(
+                        Diagnostic::error(
+                            format!("cannot order {} and {}", sorts.0, sorts.1),
+                            expr.span,
+                        )
+                        .with_help("only positions are ordered, letters are compared with `==`"),
+                    ),
+                };
+                self.diagnostics.extend(diagnostic);
+                Some(Sort::Boolean)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnostic::{Diagnostic, Location};
+    use crate::lexer::tokenize;
+    use crate::Parser;
+
+    fn errors(source: &str) -> Vec<(String, Location)> {
+        let tokens = tokenize(source).unwrap();
+        let errors: Vec<Diagnostic> = Parser::new(tokens).parse().unwrap_err();
+        errors
+            .into_iter()
+            .map(|error| (error.message, error.span.start))
+            .collect()
+    }
+
+    #[test]
+    fn test_check() {
+        let source = r#"
+for i in 0..n {
+    for j in 0..n {
+        if i.label < j.label { print(k.label) }
+        for i in n..0 { print("a") }
+        if i == "a" { print("b") }
+    }
+    var seen := n
+}
+"#;
+        let expected = [
+            ("cannot order a letter and a letter", Location::new(4, 12)),
+            (
+                "`k` is not bound by an enclosing loop",
+                Location::new(4, 38),
+            ),
+            (
+                "`i` shadows the variable of an enclosing loop",
+                Location::new(5, 13),
+            ),
+            (
+                "cannot compare a position with a string",
+                Location::new(6, 12),
+            ),
+            (
+                "expected a condition, found a position",
+                Location::new(8, 17),
+            ),
+        ];
+        let expected: Vec<(String, Location)> = expected
+            .iter()
+            .map(|(message, location)| (message.to_string(), *location))
+            .collect();
+        assert_eq!(errors(source), expected);
+
+        let source = r#"
+for i in 0..n {
+    if i.label == "a" || i.label == i.label && exists j. j < i && j.label != i.label {
+        print(i.label)
+    }
+}
+"#;
+        let tokens = tokenize(source).unwrap();
+        assert!(Parser::new(tokens).parse().is_ok());
+        assert_eq!(
+            errors("for i in 0..n { if i == \"a\" { print(i.label) } }"),
+            vec![(
+                "cannot compare a position with a string".to_string(),
+                Location::new(1, 20)
+            )]
+        );
+        // each condition is checked once, however many branches follow it
+        assert_eq!(
+            errors("for i in 0..n { if i.label < i.label { print(\"a\") } else { print(\"b\") } }"),
+            vec![(
+                "cannot order a letter and a letter".to_string(),
+                Location::new(1, 20)
+            )]
+        );
+        // the variable of a loop is only bound in its body
+        let source =
+            "for i in 0..n { print(i.label) }\nfor j in 0..n { if i == j { print(\"a\") } }\n";
+        assert_eq!(
+            errors(source),
+            vec![(
+                "`i` is not bound by an enclosing loop".to_string(),
+                Location::new(2, 20)
+            )]
+        );
+    }
+}
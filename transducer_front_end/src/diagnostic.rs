@@ -0,0 +1,134 @@
+///
+/// Source locations and error reporting for the front end
+/// (lexer and parser).
+///
+use std::fmt;
+
+/// A position in the source file. Lines and columns start at 1,
+/// columns are counted in characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// A range in the source file, `end` being exclusive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+pub struct Span {
+    pub start: Location,
+    pub end: Location,
+}
+
+impl Location {
+    pub fn new(line: usize, column: usize) -> Self {
+        Location { line, column }
+    }
+}
+
+impl Default for Location {
+    fn default() -> Self {
+        Location { line: 1, column: 1 }
+    }
+}
+
+impl Span {
+    pub fn new(start: Location, end: Location) -> Self {
+        Span { start, end }
+    }
+
+    /// The smallest span covering both `self` and `other`
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            start: std::cmp::min(self.start, other.start),
+            end: std::cmp::max(self.end, other.end),
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// An error found in the source, together with the place where it occurs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub message: String,
+    pub span: Span,
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn error(message: impl Into<String>, span: Span) -> Self {
+        Diagnostic {
+            message: message.into(),
+            span,
+            help: None,
+        }
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    /// Render the diagnostic with a snippet of the offending source line,
+    /// in the following form:
+    ///
+    /// error: unterminated string literal
+    ///  --> examples/label.txt:3:15
+    ///   |
+    /// 3 |         print("ab
+    ///   |               ^^^
+    ///
+    pub fn render(&self, source: &str, file_name: &str) -> String {
+        let line_number = self.span.start.line.to_string();
+        let gutter = " ".repeat(line_number.len());
+        let line = source
+            .lines()
+            .nth(self.span.start.line.saturating_sub(1))
+            .unwrap_or("");
+        let line_length = line.chars().count();
+
+        // underline until the end of the span, or until the end of the
+        // line when the span covers several lines
+        let start = self.span.start.column.max(1);
+        let end = if self.span.end.line == self.span.start.line {
+            self.span.end.column
+        } else {
+            line_length + 1
+        };
+        let width = end.saturating_sub(start).max(1);
+
+        let mut buf = String::new();
+        buf.push_str(&format!("error: {}\n", self.message));
+        buf.push_str(&format!("{gutter}--> {file_name}:{}\n", self.span.start));
+        buf.push_str(&format!("{gutter} |\n"));
+        buf.push_str(&format!("{line_number} | {line}\n"));
+        buf.push_str(&format!(
+            "{gutter} | {}{}\n",
+            " ".repeat(start - 1),
+            "^".repeat(width)
+        ));
+        if let Some(help) = &self.help {
+            buf.push_str(&format!("{gutter} = help: {help}\n"));
+        }
+        buf
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.span.start, self.message)
+    }
+}
+
+/// Render a list of diagnostics, separated by blank lines
+pub fn render_all(diagnostics: &[Diagnostic], source: &str, file_name: &str) -> String {
+    diagnostics
+        .iter()
+        .map(|d| d.render(source, file_name))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
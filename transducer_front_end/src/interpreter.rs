@@ -0,0 +1,267 @@
+use crate::ast::{Bexpr, InputMode, Pexpr, Stmt};
+use crate::lexer::LENGTH_VARIABLE;
+use std::collections::HashMap;
+
+// Interpreter structure
+pub struct Interpreter {
+    variables: HashMap<String, i32>,
+    flags: HashMap<String, bool>,
+    // the letters of the input word
+    word: Vec<String>,
+    n: i32,
+    output: Vec<String>,
+    // a `break` is leaving the innermost loop
+    breaking: bool,
+}
+
+impl Interpreter {
+    // Constructor for Interpreter, every Unicode scalar value of the word
+    // being a letter
+    pub fn new(word: &str) -> Self {
+        Self::with_letters(InputMode::Scalars.split(word))
+    }
+
+    // Constructor for a word of letters of any length (see
+    // `Alphabet::split_word`)
+    pub fn with_letters(word: Vec<String>) -> Self {
+        let n = word.len() as i32;
+        Self {
+            variables: HashMap::new(),
+            flags: HashMap::new(),
+            word,
+            n,
+            output: Vec::new(),
+            breaking: false,
+        }
+    }
+
+    // Main function to interpret and execute a list of statements
+    pub fn interpret(&mut self, stmts: Vec<Stmt>) {
+        print!("{}", self.run(&stmts));
+    }
+
+    // Execute a list of statements and return the printed word, whose
+    // letters are Unicode scalar values like in `new`
+    pub fn run(&mut self, stmts: &[Stmt]) -> String {
+        InputMode::Scalars.join_word(&self.run_letters(stmts))
+    }
+
+    // Execute a list of statements and return the printed letters
+    pub fn run_letters(&mut self, stmts: &[Stmt]) -> Vec<String> {
+        self.output.clear();
+        self.execute_block(stmts);
+        std::mem::take(&mut self.output)
+    }
+
+    // Function to execute a single statement
+    fn execute(&mut self, stmt: &Stmt) {
+        match stmt {
+            // Handle Print statements
+            Stmt::Print(expr, _) => {
+                let letter = self.evaluate_pexpr(expr);
+                self.output.push(letter);
+            }
+            // Handle For loops
+            Stmt::For0(var, body) => {
+                for i in 0..self.n {
+                    self.variables.insert(var.clone(), i);
+                    self.execute_block(body);
+                    if std::mem::take(&mut self.breaking) {
+                        break;
+                    }
+                }
+                self.variables.remove(var);
+            }
+
+            Stmt::For1(var, body) => {
+                for i in 0..self.n {
+                    self.variables.insert(var.clone(), self.n - i - 1);
+                    self.execute_block(body);
+                    if std::mem::take(&mut self.breaking) {
+                        break;
+                    }
+                }
+                self.variables.remove(var);
+            }
+
+            // Handle loops over constant positions, skipping those
+            // that are not in the word
+            Stmt::ForConst(var, positions, body) => {
+                for position in positions {
+                    let i = match self.evaluate_bexpr(position) {
+                        Value::Number(i) if 0 <= i && i < self.n => i,
+                        _ => continue,
+                    };
+                    self.variables.insert(var.clone(), i);
+                    self.execute_block(body);
+                    if std::mem::take(&mut self.breaking) {
+                        break;
+                    }
+                }
+                self.variables.remove(var);
+            }
+
+            // Handle fixed repetitions, whose breaks exit the enclosing loop
+            Stmt::Repeat(count, body) => {
+                for _ in 0..*count {
+                    if self.breaking {
+                        break;
+                    }
+                    self.execute_block(body);
+                }
+            }
+
+            // Handle If statements
+            Stmt::If(condition, then_branch) => {
+                if self.evaluate_condition(condition) {
+                    self.execute_block(then_branch);
+                }
+            }
+
+            // Handle boolean variables
+            Stmt::Declare(flag, value) | Stmt::Assign(flag, value) => {
+                let value = self.evaluate_condition(value);
+                self.flags.insert(flag.clone(), value);
+            }
+
+            // Leave the loop once the statements around have been skipped
+            Stmt::Break => self.breaking = true,
+        }
+    }
+
+    // Execute a block of statements
+    fn execute_block(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            if self.breaking {
+                break;
+            }
+            self.execute(stmt);
+        }
+    }
+
+    // Evaluate an expression and return a Value. The checker has sorted
+    // the expressions and bound their variables (see `check`), so the
+    // cases it rejects cannot happen here.
+    fn evaluate_bexpr(&mut self, expr: &Bexpr) -> Value {
+        match expr {
+            Bexpr::Number(n) => Value::Number(*n),
+            Bexpr::Str(s) => Value::Str(s.clone()),
+            Bexpr::True => Value::Number(1),
+            Bexpr::False => Value::Number(0),
+            Bexpr::Var(name) if name == LENGTH_VARIABLE => Value::Number(self.n),
+            Bexpr::Offset(name, offset) if name == LENGTH_VARIABLE => {
+                Value::Number(self.n + *offset)
+            }
+            Bexpr::Var(name) => Value::Number(self.position(name)),
+            Bexpr::Offset(name, offset) => Value::Number(self.position(name) + *offset),
+            Bexpr::Flag(name) => match self.flags.get(name) {
+                Some(value) => Value::Number(*value as i32),
+                None => unreachable!("boolean variable {} read before its declaration", name),
+            },
+            Bexpr::Exists(var, inner) | Bexpr::Forall(var, inner) => {
+                let exists = matches!(expr, Bexpr::Exists(_, _));
+                let previous = self.variables.remove(var);
+                let mut result = !exists;
+                for position in 0..self.n {
+                    self.variables.insert(var.clone(), position);
+                    if self.evaluate_condition(inner) == exists {
+                        result = exists;
+                        break;
+                    }
+                }
+                self.variables.remove(var);
+                if let Some(value) = previous {
+                    self.variables.insert(var.clone(), value);
+                }
+                Value::Number(result as i32)
+            }
+            Bexpr::LetterIn(name, letters) => {
+                Value::Number(letters.contains(&self.letter(name)) as i32)
+            }
+            Bexpr::Label(name) => Value::Str(self.letter(name)),
+            // Evaluate comparison expressions
+            Bexpr::LessEqual(left, right)
+            | Bexpr::Less(left, right)
+            | Bexpr::Equal(left, right)
+            | Bexpr::NotEqual(left, right)
+            | Bexpr::Greater(left, right)
+            | Bexpr::GreaterEqual(left, right) => {
+                let left_val = self.evaluate_bexpr(left);
+                let right_val = self.evaluate_bexpr(right);
+
+                match (left_val, right_val) {
+                    // Comparison between two positions
+                    (Value::Number(lv), Value::Number(rv)) => match expr {
+                        Bexpr::LessEqual(_, _) => Value::Number((lv <= rv) as i32),
+                        Bexpr::Less(_, _) => Value::Number((lv < rv) as i32),
+                        Bexpr::Equal(_, _) => Value::Number((lv == rv) as i32),
+                        Bexpr::NotEqual(_, _) => Value::Number((lv != rv) as i32),
+                        Bexpr::Greater(_, _) => Value::Number((lv > rv) as i32),
+                        Bexpr::GreaterEqual(_, _) => Value::Number((lv >= rv) as i32),
+                        _ => unreachable!(),
+                    },
+                    // Comparison of the type i.label == "some_char"
+                    (Value::Str(ls), Value::Str(rs)) => match expr {
+                        Bexpr::Equal(_, _) => Value::Number((ls == rs) as i32),
+                        Bexpr::NotEqual(_, _) => Value::Number((ls != rs) as i32),
+                        _ => unreachable!("letters are only compared with `==` and `!=`"),
+                    },
+                    _ => unreachable!("a position is compared with a letter"),
+                }
+            }
+            Bexpr::Not(expr) => Value::Number(!self.evaluate_condition(expr) as i32),
+            Bexpr::And(lhs, rhs) => {
+                let result = self.evaluate_condition(lhs) && self.evaluate_condition(rhs);
+                Value::Number(result as i32)
+            }
+            Bexpr::Or(lhs, rhs) => {
+                let result = self.evaluate_condition(lhs) || self.evaluate_condition(rhs);
+                Value::Number(result as i32)
+            }
+        }
+    }
+
+    // Evaluate a printed expression and return a letter
+    fn evaluate_pexpr(&mut self, expr: &Pexpr) -> String {
+        match expr {
+            Pexpr::Label(name) => self.letter(name),
+            Pexpr::Str(s) => s.clone(),
+            // letters that are not in the table are printed unchanged
+            Pexpr::Map(table, name) => {
+                let letter = self.letter(name);
+                match table.iter().find(|(from, _)| *from == letter) {
+                    Some((_, to)) => to.clone(),
+                    None => letter,
+                }
+            }
+        }
+    }
+
+    // The position of a loop variable
+    fn position(&self, name: &str) -> i32 {
+        match self.variables.get(name) {
+            Some(value) => *value,
+            None => unreachable!("variable {} is not bound by an enclosing loop", name),
+        }
+    }
+
+    // The letter at the position of a loop variable, which is in the word
+    fn letter(&self, name: &str) -> String {
+        self.word[self.position(name) as usize].clone()
+    }
+
+    // Evaluate a condition expression and return a boolean
+    fn evaluate_condition(&mut self, expr: &Bexpr) -> bool {
+        match self.evaluate_bexpr(expr) {
+            Value::Number(n) => n != 0,
+            Value::Str(_) => unreachable!("a string is not a condition"),
+        }
+    }
+}
+
+// Enum to represent the value of an expression
+#[derive(Debug, PartialEq, PartialOrd)]
+enum Value {
+    Number(i32),
+    Str(String),
+}
@@ -0,0 +1,607 @@
+use crate::diagnostic::{Diagnostic, Location, Span};
+use std::fmt;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Token {
+    For,
+    In,
+    Print,
+    Repeat,
+    Break,
+    Proc,
+    Transducer,
+    Main,
+    If,
+    Else,
+    Var,
+    Alphabet,
+    Class,
+    Map,
+    Exists,
+    Forall,
+    Then,
+    Do,
+    End,
+    Skip,
+    Initialize,
+    First,
+    Last,
+    True,
+    False,
+    Identifier(String),
+    Number(i32),
+    String(String),
+    LeftBrace,
+    RightBrace,
+    LeftParen,
+    RightParen,
+    LeftBracket,
+    RightBracket,
+    DotDot,
+    Dot,
+    LessEqual,
+    Less,
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    And,
+    Or,
+    Not,
+    Plus,
+    Minus,
+    Assign,
+    Define,
+    Comma,
+    Arrow,
+    LeftArrow,
+    Label(String),
+}
+
+/// A token together with its location and its text in the source
+#[derive(Debug, PartialEq, Clone)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
+    pub text: String,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::For => write!(f, "`for`"),
+            Token::In => write!(f, "`in`"),
+            Token::Print => write!(f, "`print`"),
+            Token::Repeat => write!(f, "`repeat`"),
+            Token::Break => write!(f, "`break`"),
+            Token::Proc => write!(f, "`proc`"),
+            Token::Transducer => write!(f, "`transducer`"),
+            Token::Main => write!(f, "`main`"),
+            Token::If => write!(f, "`if`"),
+            Token::Else => write!(f, "`else`"),
+            Token::Var => write!(f, "`var`"),
+            Token::Alphabet => write!(f, "`alphabet`"),
+            Token::Class => write!(f, "`class`"),
+            Token::Map => write!(f, "`map`"),
+            Token::Exists => write!(f, "`exists`"),
+            Token::Forall => write!(f, "`forall`"),
+            Token::Then => write!(f, "`then`"),
+            Token::Do => write!(f, "`do`"),
+            Token::End => write!(f, "`end`"),
+            Token::Skip => write!(f, "`skip`"),
+            Token::Initialize => write!(f, "`initialize`"),
+            Token::First => write!(f, "`first`"),
+            Token::Last => write!(f, "`last`"),
+            Token::True => write!(f, "`True`"),
+            Token::False => write!(f, "`False`"),
+            Token::Identifier(name) => write!(f, "identifier `{}`", name),
+            Token::Number(n) => write!(f, "number `{}`", n),
+            Token::String(s) => write!(f, "string \"{}\"", s),
+            Token::LeftBrace => write!(f, "`{{`"),
+            Token::RightBrace => write!(f, "`}}`"),
+            Token::LeftParen => write!(f, "`(`"),
+            Token::RightParen => write!(f, "`)`"),
+            Token::LeftBracket => write!(f, "`[`"),
+            Token::RightBracket => write!(f, "`]`"),
+            Token::DotDot => write!(f, "`..`"),
+            Token::Dot => write!(f, "`.`"),
+            Token::LessEqual => write!(f, "`<=`"),
+            Token::Less => write!(f, "`<`"),
+            Token::Equal => write!(f, "`==`"),
+            Token::NotEqual => write!(f, "`!=`"),
+            Token::Greater => write!(f, "`>`"),
+            Token::GreaterEqual => write!(f, "`>=`"),
+            Token::And => write!(f, "`&&`"),
+            Token::Or => write!(f, "`||`"),
+            Token::Not => write!(f, "`!`"),
+            Token::Plus => write!(f, "`+`"),
+            Token::Minus => write!(f, "`-`"),
+            Token::Assign => write!(f, "`:=`"),
+            Token::Define => write!(f, "`=`"),
+            Token::Comma => write!(f, "`,`"),
+            Token::Arrow => write!(f, "`->`"),
+            Token::LeftArrow => write!(f, "`<-`"),
+            Token::Label(name) => write!(f, "label `{}.label`", name),
+        }
+    }
+}
+
+// Whether the identifier following the tokens is a new name: the name
+// introduced by a binder or a parameter of a procedure
+fn binds_next(tokens: &[SpannedToken]) -> bool {
+    let mut previous = tokens.iter().rev().map(|t| &t.token);
+    let mut separator = previous.next();
+    if matches!(
+        separator,
+        Some(
+            Token::For
+                | Token::Var
+                | Token::Class
+                | Token::Map
+                | Token::Proc
+                | Token::Transducer
+                | Token::Exists
+                | Token::Forall
+                | Token::Initialize
+        )
+    ) {
+        return true;
+    }
+    // `proc name(a, b, ...`
+    loop {
+        match separator {
+            Some(Token::LeftParen) => {
+                return matches!(
+                    (previous.next(), previous.next()),
+                    (Some(Token::Identifier(_)), Some(Token::Proc))
+                )
+            }
+            Some(Token::Comma) if matches!(previous.next(), Some(Token::Identifier(_))) => {
+                separator = previous.next();
+            }
+            _ => return false,
+        }
+    }
+}
+
+// Whether the tokens end with `exists k` or `forall k`, where a `.`
+// starts the quantified condition
+fn after_quantifier(tokens: &[SpannedToken]) -> bool {
+    let mut previous = tokens.iter().rev().map(|t| &t.token);
+    matches!(
+        (previous.next(), previous.next()),
+        (
+            Some(Token::Identifier(_)),
+            Some(Token::Exists | Token::Forall)
+        )
+    )
+}
+
+// Whether the tokens end inside `main = f . g`, where the names of the
+// transducers are separated by dots
+fn in_composition(tokens: &[SpannedToken]) -> bool {
+    let mut previous = tokens.iter().rev().map(|t| &t.token);
+    loop {
+        match previous.next() {
+            Some(Token::Identifier(_) | Token::Dot) => {}
+            Some(Token::Define) => return previous.next() == Some(&Token::Main),
+            _ => return false,
+        }
+    }
+}
+
+// A character iterator that keeps track of the current line and column
+#[derive(Clone)]
+struct Cursor<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+    column: usize,
+    offset: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(input: &'a str) -> Self {
+        Cursor {
+            chars: input.chars().peekable(),
+            line: 1,
+            column: 1,
+            offset: 0,
+        }
+    }
+
+    fn peek(&mut self) -> Option<&char> {
+        self.chars.peek()
+    }
+
+    fn location(&self) -> Location {
+        Location::new(self.line, self.column)
+    }
+}
+
+impl Iterator for Cursor<'_> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        let ch = self.chars.next()?;
+        self.offset += ch.len_utf8();
+        if ch == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(ch)
+    }
+}
+
+// The variable holding the size of the input word, it cannot be bound
+pub const LENGTH_VARIABLE: &str = "n";
+
+/// The surface syntax of a program: the brace syntax, or the syntax of
+/// `semantics.md` (`for i in stdin -> do ... end`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+    Braces,
+    Paper,
+}
+
+impl Syntax {
+    /// The syntax of a file, given by its extension: `.for` files are
+    /// written in the paper syntax
+    pub fn of_file(path: &str) -> Syntax {
+        if path.ends_with(".for") {
+            Syntax::Paper
+        } else {
+            Syntax::Braces
+        }
+    }
+}
+
+pub fn tokenize(input: &str) -> Result<Vec<SpannedToken>, Vec<Diagnostic>> {
+    tokenize_with(input, Syntax::Braces)
+}
+
+/// Split a program written in the given syntax into tokens. The paper
+/// syntax adds the keywords `then`, `do`, `end`, `skip` and `initialize`,
+/// the arrow `<-` and the connectives `/\` and `\/`.
+pub fn tokenize_with(input: &str, syntax: Syntax) -> Result<Vec<SpannedToken>, Vec<Diagnostic>> {
+    let paper = syntax == Syntax::Paper;
+    let mut tokens: Vec<SpannedToken> = Vec::new();
+    let mut diagnostics = Vec::new();
+    let mut chars = Cursor::new(input);
+
+    while let Some(&ch) = chars.peek() {
+        let start = chars.location();
+        let start_offset = chars.offset;
+        let token = match ch {
+            // Skip whitespace characters
+            ' ' | '\n' | '\t' | '\r' => {
+                chars.next();
+                continue;
+            }
+            // Handle single-character tokens
+            '{' => {
+                chars.next();
+                Token::LeftBrace
+            }
+            '}' => {
+                chars.next();
+                Token::RightBrace
+            }
+            '(' => {
+                chars.next();
+                Token::LeftParen
+            }
+            ')' => {
+                chars.next();
+                Token::RightParen
+            }
+            // Handle '..' token
+            '.' => {
+                chars.next();
+                if chars.peek() == Some(&'.') {
+                    chars.next();
+                    Token::DotDot
+                } else if in_composition(&tokens) || after_quantifier(&tokens) {
+                    Token::Dot
+                } else {
+                    diagnostics.push(
+                        Diagnostic::error(
+                            "unexpected character `.`",
+                            Span::new(start, chars.location()),
+                        )
+                        .with_help("labels are written `i.label` and ranges `0..n`"),
+                    );
+                    continue;
+                }
+            }
+            // Handle comparison operators
+            '<' => {
+                chars.next();
+                if chars.peek() == Some(&'=') {
+                    chars.next();
+                    Token::LessEqual
+                } else if paper && chars.peek() == Some(&'-') {
+                    chars.next();
+                    Token::LeftArrow
+                } else {
+                    Token::Less
+                }
+            }
+            '>' => {
+                chars.next();
+                if chars.peek() == Some(&'=') {
+                    chars.next();
+                    Token::GreaterEqual
+                } else {
+                    Token::Greater
+                }
+            }
+            '=' => {
+                chars.next();
+                if chars.peek() == Some(&'=') {
+                    chars.next();
+                    Token::Equal
+                } else {
+                    Token::Define
+                }
+            }
+            ',' => {
+                chars.next();
+                Token::Comma
+            }
+            '[' => {
+                chars.next();
+                Token::LeftBracket
+            }
+            ']' => {
+                chars.next();
+                Token::RightBracket
+            }
+            // Handle offsets
+            '+' => {
+                chars.next();
+                Token::Plus
+            }
+            '-' => {
+                chars.next();
+                if chars.peek() == Some(&'>') {
+                    chars.next();
+                    Token::Arrow
+                } else {
+                    Token::Minus
+                }
+            }
+            ':' => {
+                chars.next();
+                if chars.peek() == Some(&'=') {
+                    chars.next();
+                    Token::Assign
+                } else {
+                    diagnostics.push(
+                        Diagnostic::error(
+                            "unexpected character `:`",
+                            Span::new(start, chars.location()),
+                        )
+                        .with_help("assignment is written `:=`"),
+                    );
+                    continue;
+                }
+            }
+            '!' => {
+                chars.next();
+                if chars.peek() == Some(&'=') {
+                    chars.next();
+                    Token::NotEqual
+                } else {
+                    Token::Not
+                }
+            }
+            // Handle boolean connectives
+            // `/\` and `\/`
+            '/' | '\\' if paper => {
+                chars.next();
+                match (ch, chars.peek()) {
+                    ('/', Some('\\')) => {
+                        chars.next();
+                        Token::And
+                    }
+                    ('\\', Some('/')) => {
+                        chars.next();
+                        Token::Or
+                    }
+                    _ => {
+                        diagnostics.push(
+                            Diagnostic::error(
+                                format!("unexpected character `{}`", ch),
+                                Span::new(start, chars.location()),
+                            )
+                            .with_help("the connectives are written `/\\` and `\\/`"),
+                        );
+                        continue;
+                    }
+                }
+            }
+            '&' | '|' => {
+                chars.next();
+                if chars.peek() == Some(&ch) {
+                    chars.next();
+                    if ch == '&' {
+                        Token::And
+                    } else {
+                        Token::Or
+                    }
+                } else {
+                    diagnostics.push(
+                        Diagnostic::error(
+                            format!("unexpected character `{}`", ch),
+                            Span::new(start, chars.location()),
+                        )
+                        .with_help(format!("did you mean `{}{}`?", ch, ch)),
+                    );
+                    continue;
+                }
+            }
+            // Handle string literals
+            '"' => {
+                chars.next();
+                let mut string_literal = String::new();
+                while let Some(&ch) = chars.peek() {
+                    if ch == '"' || ch == '\n' {
+                        break;
+                    } else {
+                        string_literal.push(ch);
+                        chars.next();
+                    }
+                }
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    Token::String(string_literal)
+                } else {
+                    diagnostics.push(
+                        Diagnostic::error(
+                            "unterminated string literal",
+                            Span::new(start, chars.location()),
+                        )
+                        .with_help("add a closing `\"` before the end of the line"),
+                    );
+                    continue;
+                }
+            }
+            // Handle numeric literals
+            ch if ch.is_ascii_digit() => {
+                let mut num: i32 = 0;
+                let mut overflow = false;
+                while let Some(&ch) = chars.peek() {
+                    if let Some(digit) = ch.to_digit(10) {
+                        match num
+                            .checked_mul(10)
+                            .and_then(|n| n.checked_add(digit as i32))
+                        {
+                            Some(n) => num = n,
+                            None => overflow = true,
+                        }
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                if overflow {
+                    diagnostics.push(Diagnostic::error(
+                        "number literal is too large",
+                        Span::new(start, chars.location()),
+                    ));
+                    continue;
+                }
+                Token::Number(num)
+            }
+            // Handle keywords, identifiers and labels
+            ch if ch.is_alphabetic() || ch == '_' => {
+                let mut identifier = String::new();
+                while let Some(&ch) = chars.peek() {
+                    if ch.is_alphanumeric() || ch == '_' {
+                        identifier.push(ch);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+
+                // `i.label` (but not `i..n`, nor `f.g` in a composition,
+                // nor `exists k.k < i`)
+                let mut lookahead = chars.clone();
+                if lookahead.next() == Some('.')
+                    && lookahead.peek().is_some_and(|c| c.is_alphabetic())
+                    && !in_composition(&tokens)
+                    && !matches!(
+                        tokens.last().map(|t| &t.token),
+                        Some(Token::Exists | Token::Forall)
+                    )
+                {
+                    chars.next(); // Consume the '.'
+                    let attribute_start = chars.location();
+                    let mut attribute = String::new();
+                    while let Some(&ch) = chars.peek() {
+                        if ch.is_alphanumeric() || ch == '_' {
+                            attribute.push(ch);
+                            chars.next();
+                        } else {
+                            break;
+                        }
+                    }
+                    if attribute != "label" {
+                        diagnostics.push(
+                            Diagnostic::error(
+                                format!("unknown attribute `{}`", attribute),
+                                Span::new(attribute_start, chars.location()),
+                            )
+                            .with_help("the only attribute of a position is `label`"),
+                        );
+                        continue;
+                    }
+                    Token::Label(identifier)
+                } else {
+                    match identifier.as_str() {
+                        "for" => Token::For,
+                        "in" => Token::In,
+                        "print" => Token::Print,
+                        "repeat" => Token::Repeat,
+                        "break" => Token::Break,
+                        "proc" => Token::Proc,
+                        "transducer" => Token::Transducer,
+                        "main" => Token::Main,
+                        "if" => Token::If,
+                        "else" => Token::Else,
+                        "var" => Token::Var,
+                        "alphabet" => Token::Alphabet,
+                        "class" => Token::Class,
+                        "map" => Token::Map,
+                        "exists" => Token::Exists,
+                        "forall" => Token::Forall,
+                        "then" if paper => Token::Then,
+                        "do" if paper => Token::Do,
+                        "end" if paper => Token::End,
+                        "skip" if paper => Token::Skip,
+                        "initialize" if paper => Token::Initialize,
+                        // `first` and `last` are only predicates when applied,
+                        // they stay usable as variable names
+                        "first" if chars.peek() == Some(&'(') => Token::First,
+                        "last" if chars.peek() == Some(&'(') => Token::Last,
+                        "True" => Token::True,
+                        "False" => Token::False,
+                        LENGTH_VARIABLE => {
+                            if binds_next(&tokens) {
+                                diagnostics.push(Diagnostic::error(
+                                    "`n` is reserved for the length of the input word",
+                                    Span::new(start, chars.location()),
+                                ));
+                                continue;
+                            }
+                            Token::Identifier(identifier)
+                        }
+                        _ => Token::Identifier(identifier),
+                    }
+                }
+            }
+            _ => {
+                chars.next();
+                diagnostics.push(Diagnostic::error(
+                    format!("unexpected character `{}`", ch),
+                    Span::new(start, chars.location()),
+                ));
+                continue;
+            }
+        };
+        tokens.push(SpannedToken {
+            token,
+            span: Span::new(start, chars.location()),
+            text: input[start_offset..chars.offset].to_string(),
+        });
+    }
+
+    if diagnostics.is_empty() {
+        Ok(tokens)
+    } else {
+        Err(diagnostics)
+    }
+}
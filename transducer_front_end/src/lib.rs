@@ -0,0 +1,13 @@
+pub mod ast;
+pub mod check;
+pub mod diagnostic;
+pub mod interpreter;
+pub mod lexer;
+pub mod lower;
+pub mod parser;
+pub mod surface;
+pub use ast::{Alphabet, Bexpr, InputMode, Pexpr, Program, Stmt};
+pub use diagnostic::Diagnostic;
+pub use interpreter::Interpreter;
+pub use lexer::tokenize;
+pub use parser::Parser;
@@ -0,0 +1,908 @@
+///
+/// Lowering of the checked statements of a source (see `surface` and
+/// `check`) into the statements of `ast`. The variables get a unique name
+/// at their binding, the procedures are inlined at their calls and the
+/// letter classes and maps are replaced by their letters. The loops over
+/// a range or over blocks go through every position, running their body
+/// under a guard, and the branches of an `if` are guarded by the negation
+/// of the conditions before them.
+///
+use crate::ast::{collect_bound, rename_stmt, Alphabet, Bexpr, Pexpr, Program, Stmt, MAX_UNROLLED};
+use crate::check::Bindings;
+use crate::diagnostic::{Diagnostic, Location, Span};
+use crate::lexer::LENGTH_VARIABLE;
+use crate::surface::{
+    Comparison, Expr, ExprKind, Header, Letter, LetterSet, Name, Printed, Range, Source, Statement,
+    StatementKind,
+};
+use std::collections::{HashMap, HashSet};
+
+/// Prefix of the flags introduced for else branches (the variables of the
+/// program are renamed to `X1`, `X2`, ...)
+pub const HIDDEN_FLAG_PREFIX: &str = "E";
+
+// Prefix of the variables of the program
+const VARIABLE_PREFIX: &str = "X";
+
+// Prefix of the positions quantified in the guards of block loops
+const BLOCK_BOUND_PREFIX: &str = "B";
+
+type LowerResult<T> = Result<T, Diagnostic>;
+
+/// Lower a checked source into the stages of its pipeline, in the order
+/// they run: the transducers composed by `main = f . g`, or the program
+/// itself when it does not define transducers. `names` receives the name
+/// in the source of each renamed variable. One diagnostic is collected
+/// per erroneous top-level statement.
+pub fn lower(
+    source: &Source,
+    bindings: &Bindings,
+    names: &mut HashMap<String, String>,
+) -> Result<Vec<Program>, Vec<Diagnostic>> {
+    let mut lowering = Lowering {
+        bindings,
+        names,
+        alphabet: (source.header.as_ref())
+            .map(Header::alphabet)
+            .unwrap_or_default(),
+        variables: HashMap::new(),
+        flags: HashSet::new(),
+        hidden_flags: 0,
+        classes: HashMap::new(),
+        maps: HashMap::new(),
+        procs: HashMap::new(),
+        copies: 0,
+        transducers: HashMap::new(),
+        blocks: HashMap::new(),
+        quantified: 0,
+    };
+    lowering.source(source)
+}
+
+struct Lowering<'a> {
+    bindings: &'a Bindings,
+    // name in the source of each renamed variable
+    names: &'a mut HashMap<String, String>,
+    // letters that can be read and printed
+    alphabet: Alphabet,
+    // unique name of each variable, by the place of its binding
+    variables: HashMap<Span, String>,
+    // boolean variables, by unique name
+    flags: HashSet<String>,
+    // number of flags introduced for else branches
+    hidden_flags: usize,
+    // letter classes declared with `class`
+    classes: HashMap<String, Vec<String>>,
+    // letter substitutions declared with `map`
+    maps: HashMap<String, Vec<(String, String)>>,
+    // parameters and body of the procedures declared with `proc`
+    procs: HashMap<String, (Vec<String>, Vec<Stmt>)>,
+    // number of copies of bodies made by inlining
+    copies: usize,
+    // transducers defined with `transducer`, by name
+    transducers: HashMap<String, Program>,
+    // separator of the blocks bound by `for b in blocks(stdin, "#")`
+    blocks: HashMap<String, String>,
+    // number of positions quantified in the guards of block loops
+    quantified: usize,
+}
+
+impl Lowering<'_> {
+    fn source(&mut self, source: &Source) -> Result<Vec<Program>, Vec<Diagnostic>> {
+        let mut stmts = Vec::new();
+        let mut diagnostics = Vec::new();
+        // the first statement outside of the transducers
+        let mut outside: Option<Span> = None;
+        let mut main: Option<Vec<Program>> = None;
+        for statement in &source.statements {
+            let lowered = match &statement.kind {
+                StatementKind::Transducer(name, header, body) => self
+                    .transducer(name, header.as_ref(), body)
+                    .map(|()| vec![]),
+                StatementKind::Main(_) if main.is_some() => {
+                    Err(Diagnostic::error("`main` is defined twice", statement.span))
+                }
+                StatementKind::Main(names) => self.main(names, statement.span).map(|stages| {
+                    main = Some(stages);
+                    vec![]
+                }),
+                _ => self.statement(statement),
+            };
+            match lowered {
+                Ok(lowered) => {
+                    if !lowered.is_empty() && outside.is_none() {
+                        outside = Some(statement.span);
+                    }
+                    stmts.extend(lowered)
+                }
+                Err(diagnostic) => diagnostics.push(diagnostic),
+            }
+        }
+        if diagnostics.is_empty() && !self.transducers.is_empty() {
+            if let Some(span) = outside {
+                diagnostics.push(
+                    Diagnostic::error("statement outside of a transducer", span).with_help(
+                        "the statements of a program with transducers go in their bodies",
+                    ),
+                );
+            } else if main.is_none() {
+                let end = (source.statements.last())
+                    .map_or(Location::default(), |statement| statement.span.end);
+                diagnostics.push(
+                    Diagnostic::error("missing `main`", Span::new(end, end))
+                        .with_help("compose the transducers with `main = f . g`"),
+                );
+            }
+        }
+        if !diagnostics.is_empty() {
+            return Err(diagnostics);
+        }
+        Ok(main.unwrap_or_else(|| {
+            vec![Program {
+                alphabet: self.alphabet.clone(),
+                stmts,
+            }]
+        }))
+    }
+
+    // The definition of a named transducer, which may have its own
+    // alphabet header
+    fn transducer(
+        &mut self,
+        name: &Name,
+        header: Option<&Header>,
+        body: &[Statement],
+    ) -> LowerResult<()> {
+        if self.transducers.contains_key(&name.name) {
+            return Err(Diagnostic::error(
+                format!("transducer `{}` is defined twice", name.name),
+                name.span,
+            ));
+        }
+        let alphabet = header.map_or_else(|| self.alphabet.clone(), Header::alphabet);
+        let file_alphabet = std::mem::replace(&mut self.alphabet, alphabet);
+        let stmts = self.block(body);
+        let alphabet = std::mem::replace(&mut self.alphabet, file_alphabet);
+        self.transducers.insert(
+            name.name.clone(),
+            Program {
+                alphabet,
+                stmts: stmts?,
+            },
+        );
+        Ok(())
+    }
+
+    // `main = f . g . h`, which runs `h`, then `g` on its output and `f`
+    // on the output of `g`
+    fn main(&self, names: &[Name], span: Span) -> LowerResult<Vec<Program>> {
+        let mut stages: Vec<(&String, &Program)> = Vec::new();
+        for name in names.iter().rev() {
+            match self.transducers.get(&name.name) {
+                Some(program) => stages.push((&name.name, program)),
+                None => {
+                    return Err(Diagnostic::error(
+                        format!("unknown transducer `{}`", name.name),
+                        name.span,
+                    ))
+                }
+            }
+        }
+        // each transducer reads the letters printed by the previous one
+        for pair in stages.windows(2) {
+            let ((first, printer), (second, reader)) = (pair[0], pair[1]);
+            if let Some(letter) = printer
+                .alphabet
+                .output
+                .iter()
+                .find(|letter| !reader.alphabet.input.contains(letter))
+            {
+                return Err(Diagnostic::error(
+                    format!(
+                        "`{}` prints \"{}\", which `{}` cannot read",
+                        first, letter, second
+                    ),
+                    span,
+                )
+                .with_help(format!(
+                    "add \"{}\" to the input alphabet of `{}`",
+                    letter, second
+                )));
+            }
+        }
+        Ok(stages
+            .into_iter()
+            .map(|(_, program)| program.clone())
+            .collect())
+    }
+
+    fn block(&mut self, statements: &[Statement]) -> LowerResult<Vec<Stmt>> {
+        let mut stmts = Vec::new();
+        for statement in statements {
+            stmts.extend(self.statement(statement)?);
+        }
+        Ok(stmts)
+    }
+
+    fn statement(&mut self, statement: &Statement) -> LowerResult<Vec<Stmt>> {
+        match &statement.kind {
+            // one statement per printed letter
+            StatementKind::Print(printed) => {
+                let mut stmts = Vec::new();
+                for term in printed {
+                    let letters = self.printed(term)?;
+                    stmts.extend(
+                        letters
+                            .into_iter()
+                            .map(|expr| Stmt::Print(expr, statement.span)),
+                    );
+                }
+                Ok(stmts)
+            }
+            StatementKind::For(var, range, body) => self.for_loop(var, range, body),
+            // a fixed number of repetitions, unrolled by the compiler
+            StatementKind::Repeat(count, span, body) => {
+                if *count as usize > MAX_UNROLLED {
+                    return Err(Diagnostic::error(
+                        format!("`repeat` runs its body {} times", count),
+                        *span,
+                    )
+                    .with_help(format!(
+                        "repetitions are unrolled, up to {} copies of the body",
+                        MAX_UNROLLED
+                    )));
+                }
+                Ok(vec![Stmt::Repeat(*count as usize, self.block(body)?)])
+            }
+            StatementKind::If(branches, otherwise) => {
+                let mut lowered = Vec::new();
+                for (condition, body) in branches {
+                    lowered.push((Some(self.expr(condition)?), self.block(body)?));
+                }
+                if let Some(body) = otherwise {
+                    lowered.push((None, self.block(body)?));
+                }
+                Ok(self.branches(lowered))
+            }
+            StatementKind::Declare(flag, value) => {
+                let value = self.expr(value)?;
+                Ok(vec![Stmt::Declare(self.bind(flag, true), value)])
+            }
+            // the boolean variables of the paper syntax start out false
+            StatementKind::Initialize(flag) => {
+                Ok(vec![Stmt::Declare(self.bind(flag, true), Bexpr::False)])
+            }
+            StatementKind::Assign(flag, value) => {
+                Ok(vec![Stmt::Assign(self.variable(flag), self.expr(value)?)])
+            }
+            StatementKind::Break => Ok(vec![Stmt::Break]),
+            StatementKind::Skip => Ok(vec![]),
+            // a procedure is inlined at each call
+            StatementKind::Proc(name, parameters, body) => {
+                let parameters = (parameters.iter())
+                    .map(|parameter| self.bind(parameter, false))
+                    .collect();
+                let body = self.block(body)?;
+                self.procs.insert(name.name.clone(), (parameters, body));
+                Ok(vec![])
+            }
+            StatementKind::Call(name, arguments) => self.call(name, arguments, statement.span),
+            StatementKind::Class(class, letters) => {
+                let letters = self.letters(letters)?;
+                self.classes.insert(class.name.clone(), letters);
+                Ok(vec![])
+            }
+            StatementKind::Map(map, table) => {
+                let table = self.letter_map(map, table)?;
+                self.maps.insert(map.name.clone(), table);
+                Ok(vec![])
+            }
+            StatementKind::Transducer(_, _, _) | StatementKind::Main(_) => {
+                unreachable!("the parser only reads transducers and `main` at the top level")
+            }
+        }
+    }
+
+    // A new name for the variable bound at `var`
+    fn bind(&mut self, var: &Name, flag: bool) -> String {
+        let unique = format!("{}{}", VARIABLE_PREFIX, self.variables.len() + 1);
+        self.variables.insert(var.span, unique.clone());
+        self.names.insert(unique.clone(), var.name.clone());
+        if flag {
+            self.flags.insert(unique.clone());
+        }
+        unique
+    }
+
+    // The name of a variable at one of its uses, `n` keeping its name
+    fn variable(&self, var: &Name) -> String {
+        (self.bindings.get(&var.span))
+            .and_then(|binding| self.variables.get(binding))
+            .cloned()
+            .unwrap_or_else(|| var.name.clone())
+    }
+
+    // Inline the body of a procedure, where the parameters are replaced
+    // by the arguments
+    fn call(&mut self, name: &Name, arguments: &[Name], span: Span) -> LowerResult<Vec<Stmt>> {
+        let (parameters, body) = match self.procs.get(&name.name) {
+            Some(proc) => proc.clone(),
+            None => {
+                return Err(Diagnostic::error(
+                    format!("unknown procedure `{}`", name.name),
+                    name.span,
+                ))
+            }
+        };
+        if arguments.len() != parameters.len() {
+            return Err(Diagnostic::error(
+                format!(
+                    "`{}` takes {} position{} but {} were given",
+                    name.name,
+                    parameters.len(),
+                    if parameters.len() == 1 { "" } else { "s" },
+                    arguments.len()
+                ),
+                span,
+            ));
+        }
+        let arguments = arguments.iter().map(|argument| self.variable(argument));
+        Ok(self.copy(&body, parameters.into_iter().zip(arguments).collect()))
+    }
+
+    // Copy of statements where the variables they bind get a new name,
+    // so that the copies of a body do not share variables, and the other
+    // variables are renamed by `renaming`
+    fn copy(&mut self, stmts: &[Stmt], mut renaming: HashMap<String, String>) -> Vec<Stmt> {
+        self.copies += 1;
+        let mut bound = Vec::new();
+        collect_bound(stmts, &mut bound);
+        for name in bound {
+            let renamed = format!("{}_{}", name, self.copies);
+            renaming.insert(name, renamed);
+        }
+        stmts
+            .iter()
+            .map(|stmt| rename_stmt(stmt, &renaming))
+            .collect()
+    }
+
+    fn for_loop(
+        &mut self,
+        var: &Name,
+        range: &Range,
+        body: &[Statement],
+    ) -> LowerResult<Vec<Stmt>> {
+        match range {
+            Range::Stdin { reversed } => {
+                let var = self.bind(var, false);
+                let body = self.block(body)?;
+                Ok(vec![directed(*reversed, var, body)])
+            }
+            // A block is represented by its first position, which is its
+            // separator when the block is empty; the letters after the
+            // last separator only form a block when there are some.
+            Range::Blocks {
+                separator,
+                reversed,
+            } => {
+                let separator = self.input_letter(&separator.letter, separator.span)?;
+                let var = self.bind(var, false);
+                let guard = self.block_start(&var, &separator);
+                self.blocks.insert(var.clone(), separator);
+                let body = vec![Stmt::If(guard, self.block(body)?)];
+                Ok(vec![directed(*reversed, var, body)])
+            }
+            Range::Block { block, reversed } => {
+                let block = self.variable(block);
+                let separator = self.blocks[&block].clone();
+                let var = self.bind(var, false);
+                let guard = self.block_member(&block, &var, &separator);
+                let body = vec![Stmt::If(guard, self.block(body)?)];
+                Ok(vec![directed(*reversed, var, body)])
+            }
+            Range::Positions {
+                start,
+                end,
+                inclusive,
+            } => {
+                let range = start.span.to(end.span);
+                let (start, end) = (self.expr(start)?, self.expr(end)?);
+                self.range(var, start, end, *inclusive, range, body)
+            }
+        }
+    }
+
+    // A loop over the positions from `start` to `end`
+    fn range(
+        &mut self,
+        var: &Name,
+        start: Bexpr,
+        end: Bexpr,
+        inclusive: bool,
+        range: Span,
+        body: &[Statement],
+    ) -> LowerResult<Vec<Stmt>> {
+        // The range goes down when it starts at its upper bound
+        let descending = match (bound_rank(&start), bound_rank(&end)) {
+            (
+                Some((start_rank, start_var, start_offset)),
+                Some((end_rank, end_var, end_offset)),
+            ) => {
+                if start_rank != end_rank {
+                    start_rank > end_rank
+                } else {
+                    start_var == end_var && start_offset > end_offset
+                }
+            }
+            _ => false,
+        };
+        let (lower, upper) = if descending {
+            (end, start)
+        } else {
+            (start, end)
+        };
+
+        // A range between two constants, or between two offsets of `n`,
+        // goes through its positions without reading the word, and is
+        // unrolled by the compiler
+        let positions = match constant_positions(&lower, &upper, inclusive) {
+            Some(Ok(positions)) => Some(positions),
+            Some(Err(distance)) => {
+                return Err(Diagnostic::error(
+                    format!(
+                        "the range goes {} positions away from the {} of the word",
+                        distance,
+                        if is_length(&upper) || matches!(upper, Bexpr::Offset(_, _)) {
+                            "end"
+                        } else {
+                            "start"
+                        }
+                    ),
+                    range,
+                )
+                .with_help(format!(
+                    "constant ranges are unrolled, up to {} positions",
+                    MAX_UNROLLED
+                )))
+            }
+            None => None,
+        };
+        let var = self.bind(var, false);
+        let mut body = self.block(body)?;
+        if let Some(mut positions) = positions {
+            if descending {
+                positions.reverse();
+            }
+            return Ok(vec![Stmt::ForConst(var, positions, body)]);
+        }
+
+        // Loop over every position, running the body only on the
+        // positions of the range
+        let position = Box::new(Bexpr::Var(var.clone()));
+        let mut guards = Vec::new();
+        if !matches!(lower, Bexpr::Number(0)) {
+            guards.push(Bexpr::LessEqual(Box::new(lower), position.clone()));
+        }
+        if inclusive {
+            guards.push(Bexpr::LessEqual(position, Box::new(upper)));
+        } else if !is_length(&upper) {
+            guards.push(Bexpr::Less(position, Box::new(upper)));
+        }
+        if let Some(guard) = guards
+            .into_iter()
+            .reduce(|left, right| Bexpr::And(Box::new(left), Box::new(right)))
+        {
+            body = vec![Stmt::If(guard, body)];
+        }
+        Ok(vec![directed(descending, var, body)])
+    }
+
+    // The position starts a block: it is the first position or follows
+    // a separator
+    fn block_start(&mut self, position: &str, separator: &str) -> Bexpr {
+        let previous = self.fresh_bound();
+        let follows_separator = Bexpr::Exists(
+            previous.clone(),
+            Box::new(Bexpr::And(
+                Box::new(Bexpr::Equal(
+                    Box::new(Bexpr::Offset(previous.clone(), 1)),
+                    Box::new(Bexpr::Var(position.to_string())),
+                )),
+                Box::new(Bexpr::Equal(
+                    Box::new(Bexpr::Label(previous)),
+                    Box::new(Bexpr::Str(separator.to_string())),
+                )),
+            )),
+        );
+        Bexpr::Or(
+            Box::new(Bexpr::Equal(
+                Box::new(Bexpr::Var(position.to_string())),
+                Box::new(Bexpr::Number(0)),
+            )),
+            Box::new(follows_separator),
+        )
+    }
+
+    // The position is in the block starting at `block`: it comes after
+    // the start and no separator lies between them
+    fn block_member(&mut self, block: &str, position: &str, separator: &str) -> Bexpr {
+        let between = self.fresh_bound();
+        let var = |name: &str| Box::new(Bexpr::Var(name.to_string()));
+        let separated = Bexpr::Exists(
+            between.clone(),
+            Box::new(Bexpr::And(
+                Box::new(Bexpr::And(
+                    Box::new(Bexpr::LessEqual(var(block), var(&between))),
+                    Box::new(Bexpr::LessEqual(var(&between), var(position))),
+                )),
+                Box::new(Bexpr::Equal(
+                    Box::new(Bexpr::Label(between)),
+                    Box::new(Bexpr::Str(separator.to_string())),
+                )),
+            )),
+        );
+        Bexpr::And(
+            Box::new(Bexpr::LessEqual(var(block), var(position))),
+            Box::new(separated.negated()),
+        )
+    }
+
+    // A new name for a position quantified in a guard
+    fn fresh_bound(&mut self) -> String {
+        self.quantified += 1;
+        format!("{}{}", BLOCK_BOUND_PREFIX, self.quantified)
+    }
+
+    // The statements running the first branch whose condition holds, `None`
+    // standing for the else branch. Each branch becomes an `If` guarded by
+    // its own condition and the negation of all the previous ones.
+    fn branches(&mut self, branches: Vec<(Option<Bexpr>, Vec<Stmt>)>) -> Vec<Stmt> {
+        // A branch may assign the boolean variables read by the next
+        // conditions: in that case every condition is stored in a fresh
+        // flag before its branch is executed.
+        let hidden = branches.len() > 1
+            && branches
+                .iter()
+                .any(|(condition, _)| condition.as_ref().is_some_and(Bexpr::contains_flag));
+
+        let mut statements = vec![];
+        // Conjunction of the negations of the conditions seen so far
+        let mut previous_failed: Option<Bexpr> = None;
+        for (condition, branch) in branches {
+            let mut guard = match (previous_failed.clone(), condition.clone()) {
+                (None, Some(condition)) => condition,
+                (Some(failed), Some(condition)) => {
+                    Bexpr::And(Box::new(failed), Box::new(condition))
+                }
+                (Some(failed), None) => failed,
+                (None, None) => unreachable!("the first branch has a condition"),
+            };
+            if hidden {
+                self.hidden_flags += 1;
+                let flag = format!("{}{}", HIDDEN_FLAG_PREFIX, self.hidden_flags);
+                statements.push(Stmt::Declare(flag.clone(), guard));
+                guard = Bexpr::Flag(flag);
+            }
+            if let Some(condition) = condition {
+                let failed = if hidden {
+                    guard.negated()
+                } else {
+                    condition.negated()
+                };
+                previous_failed = Some(match previous_failed {
+                    Some(previous) => Bexpr::And(Box::new(previous), Box::new(failed)),
+                    None => failed,
+                });
+            }
+            statements.push(Stmt::If(guard, branch));
+        }
+
+        statements
+    }
+
+    // The letters printed by a term of a print statement
+    fn printed(&self, term: &Printed) -> LowerResult<Vec<Pexpr>> {
+        match term {
+            // a string is a single letter when it is declared as one, and
+            // its symbols otherwise
+            Printed::Str(s) => {
+                let letters = if self.alphabet.output.contains(&s.letter) {
+                    vec![s.letter.clone()]
+                } else {
+                    self.alphabet.mode.split(&s.letter)
+                };
+                let mut printed = Vec::new();
+                for letter in letters {
+                    if !self.alphabet.output.contains(&letter) {
+                        return Err(Diagnostic::error(
+                            format!("`{}` is not in the output alphabet", letter),
+                            s.span,
+                        )
+                        .with_help("the alphabets are declared by the `alphabet` header"));
+                    }
+                    printed.push(Pexpr::Str(letter));
+                }
+                Ok(printed)
+            }
+            Printed::Label(var) => {
+                // the label may be any input letter
+                let missing = (self.alphabet.input.iter())
+                    .find(|letter| !self.alphabet.output.contains(letter));
+                if let Some(letter) = missing {
+                    return Err(Diagnostic::error(
+                        format!(
+                            "the input letter `{}` is not in the output alphabet",
+                            letter
+                        ),
+                        var.span,
+                    )
+                    .with_help(
+                        "labels can only be printed if every input letter is an output letter",
+                    ));
+                }
+                Ok(vec![Pexpr::Label(self.variable(var))])
+            }
+            Printed::Map(map, var) => match self.maps.get(&map.name) {
+                Some(table) => Ok(vec![Pexpr::Map(table.clone(), self.variable(var))]),
+                None => Err(Diagnostic::error(
+                    format!("unknown letter map `{}`", map.name),
+                    map.span,
+                )),
+            },
+        }
+    }
+
+    // A condition, or a term of a condition
+    fn expr(&mut self, expr: &Expr) -> LowerResult<Bexpr> {
+        Ok(match &expr.kind {
+            ExprKind::True => Bexpr::True,
+            ExprKind::False => Bexpr::False,
+            ExprKind::Number(k) => Bexpr::Number(*k),
+            // strings are compared with labels
+            ExprKind::Str(s) => Bexpr::Str(self.input_letter(s, expr.span)?),
+            ExprKind::Var(var) => {
+                let name = self.variable(var);
+                if self.flags.contains(&name) {
+                    Bexpr::Flag(name)
+                } else {
+                    Bexpr::Var(name)
+                }
+            }
+            ExprKind::Offset(var, offset) => Bexpr::Offset(self.variable(var), *offset),
+            ExprKind::Label(var) => Bexpr::Label(self.variable(var)),
+            // `first(i)` is `i == 0` and `last(i)` is `i + 1 == n`
+            ExprKind::First(var) => Bexpr::Equal(
+                Box::new(Bexpr::Var(self.variable(var))),
+                Box::new(Bexpr::Number(0)),
+            ),
+            ExprKind::Last(var) => Bexpr::Equal(
+                Box::new(Bexpr::Offset(self.variable(var), 1)),
+                Box::new(Bexpr::Var(LENGTH_VARIABLE.to_string())),
+            ),
+            ExprKind::In(var, set) => {
+                let letters = match set {
+                    LetterSet::Class(class) => match self.classes.get(&class.name) {
+                        Some(letters) => letters.clone(),
+                        None => {
+                            return Err(Diagnostic::error(
+                                format!("unknown letter class `{}`", class.name),
+                                class.span,
+                            ))
+                        }
+                    },
+                    LetterSet::Letters(letters) => self.letters(letters)?,
+                };
+                Bexpr::LetterIn(self.variable(var), letters)
+            }
+            ExprKind::Compare(left, comparison, _, right) => {
+                let (left, right) = (self.expr(left)?, self.expr(right)?);
+                if is_boolean(&left) || is_boolean(&right) {
+                    return Ok(boolean_comparison(*comparison, left, right));
+                }
+                let lhs = Box::new(left);
+                let rhs = Box::new(right);
+                match comparison {
+                    Comparison::LessEqual => Bexpr::LessEqual(lhs, rhs),
+                    Comparison::Less => Bexpr::Less(lhs, rhs),
+                    Comparison::Equal => Bexpr::Equal(lhs, rhs),
+                    Comparison::NotEqual => Bexpr::NotEqual(lhs, rhs),
+                    Comparison::GreaterEqual => Bexpr::GreaterEqual(lhs, rhs),
+                    Comparison::Greater => Bexpr::Greater(lhs, rhs),
+                }
+            }
+            ExprKind::Not(inner) => Bexpr::Not(Box::new(self.expr(inner)?)),
+            ExprKind::And(left, right) => {
+                Bexpr::And(Box::new(self.expr(left)?), Box::new(self.expr(right)?))
+            }
+            ExprKind::Or(left, right) => {
+                Bexpr::Or(Box::new(self.expr(left)?), Box::new(self.expr(right)?))
+            }
+            ExprKind::Exists(var, inner) => {
+                let var = self.bind(var, false);
+                Bexpr::Exists(var, Box::new(self.expr(inner)?))
+            }
+            ExprKind::Forall(var, inner) => {
+                let var = self.bind(var, false);
+                Bexpr::Forall(var, Box::new(self.expr(inner)?))
+            }
+            ExprKind::Paren(inner) => self.expr(inner)?,
+        })
+    }
+
+    // A letter of the input alphabet
+    fn input_letter(&self, letter: &str, span: Span) -> LowerResult<String> {
+        if self.alphabet.input.iter().any(|input| input == letter) {
+            Ok(letter.to_string())
+        } else {
+            Err(Diagnostic::error(
+                format!("\"{}\" is not a letter of the input alphabet", letter),
+                span,
+            )
+            .with_help("the alphabets are declared by the `alphabet` header"))
+        }
+    }
+
+    // A set of input letters `{"a", "b"}`
+    fn letters(&self, set: &[Letter]) -> LowerResult<Vec<String>> {
+        let mut letters = Vec::new();
+        for letter in set {
+            let letter = self.input_letter(&letter.letter, letter.span)?;
+            if !letters.contains(&letter) {
+                letters.push(letter);
+            }
+        }
+        Ok(letters)
+    }
+
+    // A letter substitution `{a -> b, "#" -> a}` from input letters to
+    // output letters, where the letters that are not mapped are kept
+    fn letter_map(
+        &self,
+        map: &Name,
+        table: &[(Letter, Letter)],
+    ) -> LowerResult<Vec<(String, String)>> {
+        let mut lowered: Vec<(String, String)> = Vec::new();
+        for (from, to) in table {
+            if !self.alphabet.input.contains(&from.letter) {
+                return Err(Diagnostic::error(
+                    format!("`{}` is not a letter of the input alphabet", from.letter),
+                    from.span,
+                ));
+            }
+            if lowered.iter().any(|(letter, _)| *letter == from.letter) {
+                return Err(Diagnostic::error(
+                    format!("`{}` is mapped twice", from.letter),
+                    from.span,
+                ));
+            }
+            if !self.alphabet.output.contains(&to.letter) {
+                return Err(Diagnostic::error(
+                    format!("`{}` is not in the output alphabet", to.letter),
+                    to.span,
+                ));
+            }
+            lowered.push((from.letter.clone(), to.letter.clone()));
+        }
+
+        let unmapped = (self.alphabet.input.iter()).find(|letter| {
+            !lowered.iter().any(|(from, _)| from == *letter)
+                && !self.alphabet.output.contains(letter)
+        });
+        if let Some(letter) = unmapped {
+            return Err(Diagnostic::error(
+                format!(
+                    "the input letter `{}` is not mapped by `{}` and is not in the output alphabet",
+                    letter, map.name
+                ),
+                map.span,
+            )
+            .with_help(format!("add `{} -> ...` to the map", letter)));
+        }
+        Ok(lowered)
+    }
+}
+
+// A loop from the first position to the last, or from the last to the
+// first when it is reversed
+fn directed(reversed: bool, var: String, body: Vec<Stmt>) -> Stmt {
+    if reversed {
+        Stmt::For1(var, body)
+    } else {
+        Stmt::For0(var, body)
+    }
+}
+
+// Compare two booleans, which the checker only allows with `==` and
+// `!=`. The comparison is turned into a formula on the operands.
+fn boolean_comparison(comparison: Comparison, left: Bexpr, right: Bexpr) -> Bexpr {
+    let constant = |expr: &Bexpr| match expr {
+        Bexpr::True => Some(true),
+        Bexpr::False => Some(false),
+        _ => None,
+    };
+    let equal = match (constant(&left), constant(&right)) {
+        (_, Some(true)) => left,
+        (_, Some(false)) => left.negated(),
+        (Some(true), None) => right,
+        (Some(false), None) => right.negated(),
+        (None, None) => Bexpr::Or(
+            Box::new(Bexpr::And(Box::new(left.clone()), Box::new(right.clone()))),
+            Box::new(Bexpr::And(
+                Box::new(left.negated()),
+                Box::new(right.negated()),
+            )),
+        ),
+    };
+    match comparison {
+        Comparison::Equal => equal,
+        Comparison::NotEqual => equal.negated(),
+        _ => unreachable!("booleans are only compared with `==` and `!=`"),
+    }
+}
+
+// Whether the expression is a boolean (a condition, a flag or a
+// constant) rather than a position or a letter
+fn is_boolean(expr: &Bexpr) -> bool {
+    !matches!(
+        expr,
+        Bexpr::Var(_) | Bexpr::Str(_) | Bexpr::Label(_) | Bexpr::Offset(_, _) | Bexpr::Number(_)
+    )
+}
+
+// Whether the bound is `n`, the first position after the word
+fn is_length(bound: &Bexpr) -> bool {
+    matches!(bound, Bexpr::Var(name) if name == LENGTH_VARIABLE)
+}
+
+// Order of the bounds of a range that can be compared without knowing
+// the positions: constants come first, then the variables, then `n`.
+// Bounds on two different variables are not ordered.
+fn bound_rank(bound: &Bexpr) -> Option<(u8, Option<&str>, i32)> {
+    match bound {
+        Bexpr::Number(k) => Some((0, None, *k)),
+        Bexpr::Var(name) if name == LENGTH_VARIABLE => Some((2, None, 0)),
+        Bexpr::Offset(name, k) if name == LENGTH_VARIABLE => Some((2, None, *k)),
+        Bexpr::Var(name) => Some((1, Some(name), 0)),
+        Bexpr::Offset(name, k) => Some((1, Some(name), *k)),
+        _ => None,
+    }
+}
+
+// The positions between two bounds on the same end of the word, from
+// the lower one, or `None` when the bounds depend on the input. The
+// error is the distance to that end of the farthest position, when it
+// is too far for the loop to be unrolled.
+fn constant_positions(
+    lower: &Bexpr,
+    upper: &Bexpr,
+    inclusive: bool,
+) -> Option<Result<Vec<Bexpr>, i64>> {
+    let (from_end, start, end) = match (bound_rank(lower)?, bound_rank(upper)?) {
+        ((0, _, start), (0, _, end)) => (false, start as i64, end as i64),
+        ((2, _, start), (2, _, end)) => (true, start as i64, end as i64),
+        _ => return None,
+    };
+    let end = if inclusive { end + 1 } else { end };
+    // the offsets of the positions in the word, `n - 1` being the last one
+    let (start, end) = if from_end {
+        (start, end.min(0))
+    } else {
+        (start.max(0), end)
+    };
+    if start < end {
+        let distance = if from_end { -start } else { end };
+        if distance > MAX_UNROLLED as i64 {
+            return Some(Err(distance));
+        }
+    }
+    Some(Ok((start..end)
+        .map(|offset| {
+            if from_end {
+                Bexpr::Offset(LENGTH_VARIABLE.to_string(), offset as i32)
+            } else {
+                Bexpr::Number(offset as i32)
+            }
+        })
+        .collect()))
+}
@@ -0,0 +1,1582 @@
+use crate::ast::{Bexpr, InputMode, Pexpr, Program, Stmt};
+use crate::check::check;
+use crate::diagnostic::{Diagnostic, Span};
+use crate::lexer::{SpannedToken, Syntax, Token};
+use crate::lower::lower;
+use crate::surface::{
+    Comparison, Expr, ExprKind, Header, Letter, LetterSet, Name, Printed, Range, Section, Source,
+    Statement, StatementKind,
+};
+use std::collections::HashMap;
+
+pub struct Parser {
+    tokens: Vec<SpannedToken>,
+    current: usize,
+    // number of braces opened around the current token
+    depth: usize,
+    // number of loops around the current statement
+    loops: usize,
+    // name in the source of the variables renamed by `lower`
+    names: HashMap<String, String>,
+}
+
+type ParseResult<T> = Result<T, Diagnostic>;
+
+impl Parser {
+    // Create a new parser with a list of tokens
+    pub fn new(tokens: Vec<SpannedToken>) -> Self {
+        Parser {
+            tokens,
+            current: 0,
+            depth: 0,
+            loops: 0,
+            names: HashMap::new(),
+        }
+    }
+
+    // Parse the tokens into a program, collecting one diagnostic per
+    // erroneous top-level statement
+    pub fn parse(&mut self) -> Result<Program, Vec<Diagnostic>> {
+        let (source, mut stages) = self.stages(Syntax::Braces)?;
+        if stages.len() == 1 {
+            return Ok(stages.remove(0));
+        }
+        let main = (source.statements.iter())
+            .find(|statement| matches!(statement.kind, StatementKind::Main(_)));
+        Err(vec![Diagnostic::error(
+            "`main` composes several transducers",
+            main.map_or_else(|| self.end_of_file_span(), |statement| statement.span),
+        )
+        .with_help(
+            "both command line tools run the transducers composed by `main` one after the other",
+        )])
+    }
+
+    // Parse the tokens into the stages of a pipeline, in the order they
+    // run: the transducers composed by `main = f . g`, or the program
+    // itself when it does not define transducers
+    pub fn parse_pipeline(&mut self) -> Result<Vec<Program>, Vec<Diagnostic>> {
+        self.stages(Syntax::Braces).map(|(_, stages)| stages)
+    }
+
+    // Parse the tokens of a program written in the paper syntax (see
+    // `semantics.md`), collecting one diagnostic per erroneous top-level
+    // command
+    pub fn parse_paper(&mut self) -> Result<Program, Vec<Diagnostic>> {
+        let (_, mut stages) = self.stages(Syntax::Paper)?;
+        Ok(stages.remove(0))
+    }
+
+    /// Parse the tokens into the statements as they are written, without
+    /// checking nor lowering them
+    pub fn parse_source(&mut self, syntax: Syntax) -> Result<Source, Vec<Diagnostic>> {
+        let (source, diagnostics) = self.source(syntax);
+        if diagnostics.is_empty() {
+            Ok(source)
+        } else {
+            Err(diagnostics)
+        }
+    }
+
+    /// The name in the source of the variables of the parsed programs,
+    /// which are renamed `X1`, `X2`, ... (see `lower`)
+    pub fn names(&self) -> &HashMap<String, String> {
+        &self.names
+    }
+
+    // Parse, check and lower the tokens. The statements that parse are
+    // checked even when others do not, the diagnostics coming in the
+    // order of the source.
+    fn stages(&mut self, syntax: Syntax) -> Result<(Source, Vec<Program>), Vec<Diagnostic>> {
+        let (source, mut diagnostics) = self.source(syntax);
+        let (bindings, errors) = check(&source);
+        diagnostics.extend(errors);
+        if !diagnostics.is_empty() {
+            diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+            return Err(diagnostics);
+        }
+        let stages = lower(&source, &bindings, &mut self.names)?;
+        Ok((source, stages))
+    }
+
+    // Parse the header and the top-level statements, collecting one
+    // diagnostic per erroneous statement
+    fn source(&mut self, syntax: Syntax) -> (Source, Vec<Diagnostic>) {
+        let mut statements = Vec::new();
+        let mut diagnostics = Vec::new();
+        let mut header = None;
+        if self.check(Token::Alphabet) {
+            match self.alphabet() {
+                Ok(parsed) => header = Some(parsed),
+                Err(diagnostic) => {
+                    diagnostics.push(diagnostic);
+                    self.synchronize();
+                }
+            }
+        }
+        while self.current < self.tokens.len() {
+            let parsed = match (syntax, self.peek()) {
+                (Syntax::Paper, _) => self.command(),
+                (Syntax::Braces, Some(Token::Transducer)) => self.transducer(),
+                (Syntax::Braces, Some(Token::Main)) => self.main(),
+                (Syntax::Braces, _) => self.statement(),
+            };
+            match parsed {
+                Ok(statement) => statements.push(statement),
+                Err(diagnostic) => {
+                    diagnostics.push(diagnostic);
+                    self.synchronize();
+                }
+            }
+        }
+        let source = Source {
+            syntax,
+            header,
+            statements,
+        };
+        (source, diagnostics)
+    }
+
+    // Parse a command of the paper syntax
+    fn command(&mut self) -> ParseResult<Statement> {
+        let start = self.current_span();
+        let kind = match self.peek().cloned() {
+            Some(Token::Skip) => {
+                self.current += 1;
+                StatementKind::Skip
+            }
+            Some(Token::Print) => return self.statement(),
+            // `for i in stdin -> do ... end` goes from the first position
+            // to the last, `<-` from the last to the first
+            Some(Token::For) => {
+                self.current += 1;
+                let var = match self.peek() {
+                    Some(Token::Identifier(_)) => self.identifier("identifier")?,
+                    _ => return Err(self.error_here("expected identifier after `for`")),
+                };
+                self.expect(Token::In)?;
+                if !(self.peek().is_some() && self.tokens[self.current].text == "stdin") {
+                    return Err(self.unexpected("`stdin`"));
+                }
+                self.current += 1;
+                let reversed = match self.peek() {
+                    Some(Token::Arrow) => false,
+                    Some(Token::LeftArrow) => true,
+                    _ => return Err(self.unexpected("`->` or `<-`")),
+                };
+                self.current += 1;
+                self.expect(Token::Do)?;
+                self.loops += 1;
+                let body = self.commands();
+                self.loops -= 1;
+                let body = body?;
+                self.expect(Token::End)?;
+                StatementKind::For(var, Range::Stdin { reversed }, body)
+            }
+            Some(Token::If) => {
+                self.current += 1;
+                let condition = self.b_expression()?;
+                self.expect(Token::Then)?;
+                let branches = vec![(condition, self.commands()?)];
+                let mut otherwise = None;
+                if self.check(Token::Else) {
+                    self.current += 1;
+                    otherwise = Some(self.commands()?);
+                }
+                self.expect(Token::End)?;
+                StatementKind::If(branches, otherwise)
+            }
+            // the boolean variables start out false
+            Some(Token::Initialize) => {
+                self.current += 1;
+                StatementKind::Initialize(self.identifier("identifier after `initialize`")?)
+            }
+            Some(Token::Identifier(_))
+                if matches!(
+                    self.tokens.get(self.current + 1).map(|t| &t.token),
+                    Some(Token::Define)
+                ) =>
+            {
+                let flag = self.identifier("identifier")?;
+                self.current += 1;
+                StatementKind::Assign(flag, self.b_expression()?)
+            }
+            _ => return Err(self.unexpected("command")),
+        };
+        Ok(Statement {
+            kind,
+            span: start.to(self.previous_span()),
+        })
+    }
+
+    // Parse the commands of a block of the paper syntax, up to its `end`
+    // or `else`
+    fn commands(&mut self) -> ParseResult<Vec<Statement>> {
+        self.depth += 1;
+        let mut body = Vec::new();
+        while !self.check(Token::End) && !self.check(Token::Else) {
+            if self.peek().is_none() {
+                return Err(self.error_here("expected `end`, found end of file"));
+            }
+            body.push(self.command()?);
+        }
+        self.depth -= 1;
+        Ok(body)
+    }
+
+    // Parse the definition of a named transducer, which may have its own
+    // alphabet header
+    fn transducer(&mut self) -> ParseResult<Statement> {
+        let start = self.current_span();
+        self.current += 1;
+        let name = self.identifier("identifier after `transducer`")?;
+        self.expect(Token::LeftBrace)?;
+        let header = if self.check(Token::Alphabet) {
+            Some(self.alphabet()?)
+        } else {
+            None
+        };
+        let body = self.block_rest()?;
+        Ok(Statement {
+            kind: StatementKind::Transducer(name, header, body),
+            span: start.to(self.previous_span()),
+        })
+    }
+
+    // Parse `main = f . g . h`, which runs `h`, then `g` on its output
+    // and `f` on the output of `g`
+    fn main(&mut self) -> ParseResult<Statement> {
+        let start = self.current_span();
+        self.current += 1;
+        self.expect(Token::Define)?;
+        let mut names = Vec::new();
+        loop {
+            names.push(self.identifier("name of a transducer")?);
+            if !self.check(Token::Dot) {
+                break;
+            }
+            self.current += 1;
+        }
+        Ok(Statement {
+            kind: StatementKind::Main(names),
+            span: start.to(self.previous_span()),
+        })
+    }
+
+    // Parse the `alphabet { input "ab#" output "ab" }` header, where the
+    // letters are the symbols of the strings and a missing section is
+    // the same as the other one. The symbols are the characters, unless a
+    // `mode bytes` or `mode graphemes` section comes first.
+    fn alphabet(&mut self) -> ParseResult<Header> {
+        self.expect(Token::Alphabet)?;
+        let header = self.previous_span();
+        self.expect(Token::LeftBrace)?;
+        let mut input: Option<Section> = None;
+        let mut output: Option<Section> = None;
+        let mut mode: Option<InputMode> = None;
+        while !self.check(Token::RightBrace) {
+            let section = match self.tokens.get(self.current) {
+                Some(t) if ["input", "output", "mode"].contains(&t.text.as_str()) => t.text.clone(),
+                _ => return Err(self.unexpected("`input`, `output`, `mode` or `}`")),
+            };
+            let section_span = self.current_span();
+            self.current += 1;
+            if section == "mode" {
+                if mode.is_some() {
+                    return Err(Diagnostic::error(
+                        "the mode is declared twice",
+                        section_span,
+                    ));
+                }
+                if input.is_some() || output.is_some() {
+                    return Err(Diagnostic::error(
+                        "the mode is declared after the letters",
+                        section_span,
+                    )
+                    .with_help("declare the mode before `input` and `output`"));
+                }
+                let name = self.tokens.get(self.current).map(|t| t.text.clone());
+                match name.as_deref().and_then(InputMode::from_name) {
+                    Some(declared) => mode = Some(declared),
+                    None => {
+                        return Err(self
+                            .unexpected("input mode")
+                            .with_help(format!("the modes are {}", InputMode::NAMES.join(", "))))
+                    }
+                }
+                self.current += 1;
+                continue;
+            }
+            // the letters are the symbols of a string, or the strings of a
+            // list `{"open", "close"}`
+            let start = self.current_span();
+            let written = match self.peek().cloned() {
+                Some(Token::String(s)) => {
+                    self.current += 1;
+                    Section::Symbols(s)
+                }
+                Some(Token::LeftBrace) => Section::List(self.symbols()?),
+                _ => return Err(self.unexpected("string of letters or list of letters")),
+            };
+            let letters_span = start.to(self.previous_span());
+
+            let mut alphabet: Vec<String> = Vec::new();
+            for letter in written.letters(mode.unwrap_or_default()) {
+                if alphabet.contains(&letter) {
+                    return Err(Diagnostic::error(
+                        format!("letter `{}` is declared twice", letter),
+                        letters_span,
+                    ));
+                }
+                alphabet.push(letter);
+            }
+            if alphabet.is_empty() {
+                return Err(Diagnostic::error(
+                    format!("the {} alphabet is empty", section),
+                    letters_span,
+                ));
+            }
+            let slot = if section == "input" {
+                &mut input
+            } else {
+                &mut output
+            };
+            if slot.replace(written).is_some() {
+                return Err(Diagnostic::error(
+                    format!("the {} alphabet is declared twice", section),
+                    section_span,
+                ));
+            }
+        }
+        self.expect(Token::RightBrace)?;
+        if input.is_none() && output.is_none() {
+            return Err(Diagnostic::error("the alphabet is empty", header)
+                .with_help("declare the letters with `input \"ab\"` and `output \"ab\"`"));
+        }
+        Ok(Header {
+            mode,
+            input,
+            output,
+            span: header.to(self.previous_span()),
+        })
+    }
+
+    // Parse a list of letters `{"open", "close"}`, which may have several
+    // characters but neither whitespace nor commas, which separate the
+    // letters of the input words
+    fn symbols(&mut self) -> ParseResult<Vec<String>> {
+        self.expect(Token::LeftBrace)?;
+        let mut letters = Vec::new();
+        while !self.check(Token::RightBrace) {
+            if !letters.is_empty() {
+                self.expect(Token::Comma)?;
+            }
+            match self.peek().cloned() {
+                Some(Token::String(s))
+                    if !s.is_empty() && !s.contains(|c: char| c.is_whitespace() || c == ',') =>
+                {
+                    self.current += 1;
+                    letters.push(s);
+                }
+                Some(Token::String(s)) => {
+                    return Err(self
+                        .error_here(format!("`{}` is not a letter", s))
+                        .with_help("letters are not empty and have neither whitespace nor commas"))
+                }
+                _ => return Err(self.unexpected("letter")),
+            }
+        }
+        self.current += 1;
+        Ok(letters)
+    }
+
+    // Parse a single statement
+    fn statement(&mut self) -> ParseResult<Statement> {
+        let start = self.current_span();
+        let kind = match self.peek().cloned() {
+            // Parse a print statement
+            Some(Token::Print) => {
+                self.current += 1;
+                self.expect(Token::LeftParen)?;
+                let printed = self.p_expression()?;
+                self.expect(Token::RightParen)?;
+                StatementKind::Print(printed)
+            }
+
+            // Parse a for loop
+            Some(Token::For) => {
+                self.current += 1;
+                let var = match self.peek() {
+                    Some(Token::Identifier(_)) => self.identifier("identifier")?,
+                    _ => return Err(self.error_here("expected identifier after `for`")),
+                };
+                self.expect(Token::In)?;
+                let range = self.range()?;
+                StatementKind::For(var, range, self.loop_body()?)
+            }
+            // Parse a fixed number of repetitions, unrolled by the compiler
+            Some(Token::Repeat) => {
+                self.current += 1;
+                let count = match self.peek().cloned() {
+                    Some(Token::Number(count)) => {
+                        self.current += 1;
+                        count
+                    }
+                    _ => return Err(self.unexpected("number of repetitions")),
+                };
+                let count_span = self.previous_span();
+                StatementKind::Repeat(count, count_span, self.block()?)
+            }
+            // Parse the definition of a procedure, which is inlined at
+            // each call
+            Some(Token::Proc) => {
+                if self.depth > 0 {
+                    return Err(self.error_here("procedures are defined at the top level"));
+                }
+                self.current += 1;
+                let name = self.identifier("identifier after `proc`")?;
+                self.expect(Token::LeftParen)?;
+                let mut parameters = Vec::new();
+                while !self.check(Token::RightParen) {
+                    if !parameters.is_empty() {
+                        self.expect(Token::Comma)?;
+                    }
+                    parameters.push(self.identifier("parameter")?);
+                }
+                self.expect(Token::RightParen)?;
+
+                // The body cannot break out of the loops around its calls
+                let outer_loops = std::mem::replace(&mut self.loops, 0);
+                let body = self.block();
+                self.loops = outer_loops;
+                StatementKind::Proc(name, parameters, body?)
+            }
+            // Parse an exit of the innermost loop
+            Some(Token::Break) => {
+                if self.loops == 0 {
+                    return Err(self.error_here("`break` outside of a loop"));
+                }
+                self.current += 1;
+                StatementKind::Break
+            }
+            // Parse an if statement
+            Some(Token::If) => self.if_statement()?,
+            // Parse the declaration of a letter class
+            Some(Token::Class) => {
+                if self.depth > 0 {
+                    return Err(self.error_here("letter classes are declared at the top level"));
+                }
+                self.current += 1;
+                let class = self.identifier("identifier after `class`")?;
+                self.expect(Token::Define)?;
+                StatementKind::Class(class, self.letter_set()?)
+            }
+            // Parse the declaration of a letter substitution
+            Some(Token::Map) => {
+                if self.depth > 0 {
+                    return Err(self.error_here("letter maps are declared at the top level"));
+                }
+                self.current += 1;
+                let map = self.identifier("identifier after `map`")?;
+                self.expect(Token::Define)?;
+                StatementKind::Map(map, self.letter_map()?)
+            }
+            // Parse the declaration of a boolean variable
+            Some(Token::Var) => {
+                self.current += 1;
+                let flag = self.identifier("identifier after `var`")?;
+                self.expect(Token::Assign)?;
+                StatementKind::Declare(flag, self.b_expression()?)
+            }
+            // Parse a call to a procedure or the assignment of a boolean
+            // variable
+            Some(Token::Identifier(_)) => {
+                let name = self.identifier("statement")?;
+                if self.check(Token::LeftParen) {
+                    self.call(name)?
+                } else if self.check(Token::Assign) {
+                    self.current += 1;
+                    StatementKind::Assign(name, self.b_expression()?)
+                } else {
+                    self.current -= 1;
+                    return Err(self.unexpected("statement"));
+                }
+            }
+            _ => return Err(self.unexpected("statement")),
+        };
+        Ok(Statement {
+            kind,
+            span: start.to(self.previous_span()),
+        })
+    }
+
+    // Parse the arguments of a call to a procedure
+    fn call(&mut self, name: Name) -> ParseResult<StatementKind> {
+        self.expect(Token::LeftParen)?;
+        let mut arguments = Vec::new();
+        while !self.check(Token::RightParen) {
+            if !arguments.is_empty() {
+                self.expect(Token::Comma)?;
+            }
+            arguments.push(self.identifier("position variable")?);
+        }
+        self.expect(Token::RightParen)?;
+        Ok(StatementKind::Call(name, arguments))
+    }
+
+    // Parse the positions of a for loop: the blocks of the input,
+    // `blocks(stdin, "#")`, or the positions of a block `b`, either of
+    // them possibly reversed by `rev(...)`, or a range `a..b`
+    fn range(&mut self) -> ParseResult<Range> {
+        let reversed = self.check_call("rev");
+        if reversed {
+            self.current += 2;
+        }
+        let range = if self.check_call("blocks") {
+            self.current += 2;
+            if !(self.peek().is_some() && self.tokens[self.current].text == "stdin") {
+                return Err(self.unexpected("`stdin`"));
+            }
+            self.current += 1;
+            self.expect(Token::Comma)?;
+            let separator = match self.peek().cloned() {
+                Some(Token::String(letter)) => {
+                    self.current += 1;
+                    Letter {
+                        letter,
+                        quoted: true,
+                        span: self.previous_span(),
+                    }
+                }
+                _ => return Err(self.unexpected("letter")),
+            };
+            self.expect(Token::RightParen)?;
+            Range::Blocks {
+                separator,
+                reversed,
+            }
+        } else if reversed
+            || matches!(self.peek(), Some(Token::Identifier(_)))
+                && matches!(
+                    self.tokens.get(self.current + 1).map(|t| &t.token),
+                    Some(Token::LeftBrace)
+                )
+        {
+            let block = self.identifier("`blocks(stdin, ...)` or a block")?;
+            Range::Block { block, reversed }
+        } else {
+            let start = self.range_bound()?;
+            self.expect(Token::DotDot)?;
+            let inclusive = self.check(Token::Define);
+            if inclusive {
+                self.current += 1;
+            }
+            let end = self.range_bound()?;
+            return Ok(Range::Positions {
+                start: Box::new(start),
+                end: Box::new(end),
+                inclusive,
+            });
+        };
+        if reversed {
+            self.expect(Token::RightParen)?;
+        }
+        Ok(range)
+    }
+
+    // Parse the body of a loop, which `break` exits
+    fn loop_body(&mut self) -> ParseResult<Vec<Statement>> {
+        self.loops += 1;
+        let body = self.block();
+        self.loops -= 1;
+        body
+    }
+
+    // Parse a list of statements enclosed in braces
+    fn block(&mut self) -> ParseResult<Vec<Statement>> {
+        self.expect(Token::LeftBrace)?;
+        self.block_rest()
+    }
+
+    // Parse the statements of a block after its opening brace
+    fn block_rest(&mut self) -> ParseResult<Vec<Statement>> {
+        self.depth += 1;
+        let mut body = Vec::new();
+        while !self.check(Token::RightBrace) {
+            if self.peek().is_none() {
+                return Err(self.error_here("expected `}`, found end of file"));
+            }
+            body.push(self.statement()?);
+        }
+        self.expect(Token::RightBrace)?;
+        self.depth -= 1;
+        Ok(body)
+    }
+
+    // Parse an if statement, possibly followed by `else if` and `else`
+    // branches
+    fn if_statement(&mut self) -> ParseResult<StatementKind> {
+        self.current += 1;
+
+        let condition = self.b_expression()?;
+        let then_branch = self.block()?;
+        let mut branches = vec![(condition, then_branch)];
+        let mut otherwise = None;
+
+        while let Some(Token::Else) = self.peek() {
+            // Skip over "else" token
+            self.current += 1;
+
+            if self.check(Token::If) {
+                self.current += 1;
+                let condition = self.b_expression()?;
+                let branch = self.block()?;
+                branches.push((condition, branch));
+            } else {
+                // Parse the else branch, which ends the cascade
+                otherwise = Some(self.block()?);
+                break;
+            }
+        }
+
+        Ok(StatementKind::If(branches, otherwise))
+    }
+
+    // Parse a print expression, a concatenation `e1 + e2 + ...` of
+    // strings and labels
+    fn p_expression(&mut self) -> ParseResult<Vec<Printed>> {
+        let mut printed = vec![self.p_term()?];
+        while self.check(Token::Plus) {
+            self.current += 1;
+            printed.push(self.p_term()?);
+        }
+        Ok(printed)
+    }
+
+    // Parse a string, a label or a mapped label
+    fn p_term(&mut self) -> ParseResult<Printed> {
+        match self.peek().cloned() {
+            Some(Token::String(letter)) => {
+                self.current += 1;
+                Ok(Printed::Str(Letter {
+                    letter,
+                    quoted: true,
+                    span: self.previous_span(),
+                }))
+            }
+            Some(Token::Label(name)) => {
+                self.current += 1;
+                Ok(Printed::Label(Name {
+                    name,
+                    span: self.previous_span(),
+                }))
+            }
+            // `swap[i.label]`
+            Some(Token::Identifier(_))
+                if matches!(
+                    self.tokens.get(self.current + 1).map(|t| &t.token),
+                    Some(Token::LeftBracket)
+                ) =>
+            {
+                let map = self.identifier("letter map")?;
+                self.expect(Token::LeftBracket)?;
+                let label = match self.peek().cloned() {
+                    Some(Token::Label(name)) => {
+                        self.current += 1;
+                        Name {
+                            name,
+                            span: self.previous_span(),
+                        }
+                    }
+                    _ => return Err(self.unexpected("label")),
+                };
+                self.expect(Token::RightBracket)?;
+                Ok(Printed::Map(map, label))
+            }
+            _ => Err(self.unexpected("string or label")),
+        }
+    }
+
+    // Parse a boolean expression, the connectives are given by
+    // increasing precedence: `||`, `&&`, `!`, then comparisons
+    fn b_expression(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.conjunction()?;
+        while self.check(Token::Or) {
+            self.current += 1;
+            let right = self.conjunction()?;
+            let span = expr.span.to(right.span);
+            expr = Expr {
+                kind: ExprKind::Or(Box::new(expr), Box::new(right)),
+                span,
+            };
+        }
+        Ok(expr)
+    }
+
+    // Parse a conjunction of (possibly negated) comparisons
+    fn conjunction(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.negation()?;
+        while self.check(Token::And) {
+            self.current += 1;
+            let right = self.negation()?;
+            let span = expr.span.to(right.span);
+            expr = Expr {
+                kind: ExprKind::And(Box::new(expr), Box::new(right)),
+                span,
+            };
+        }
+        Ok(expr)
+    }
+
+    // Parse a negation, or a quantified condition `exists k. ...` whose
+    // body extends as far to the right as possible
+    fn negation(&mut self) -> ParseResult<Expr> {
+        let start = self.current_span();
+        if self.check(Token::Not) {
+            self.current += 1;
+            let inner = self.negation()?;
+            let span = start.to(inner.span);
+            Ok(Expr {
+                kind: ExprKind::Not(Box::new(inner)),
+                span,
+            })
+        } else if self.check(Token::Exists) || self.check(Token::Forall) {
+            let exists = self.check(Token::Exists);
+            self.current += 1;
+            let var = self.identifier("position variable")?;
+            self.expect(Token::Dot)?;
+            let body = Box::new(self.b_expression()?);
+            let span = start.to(body.span);
+            let kind = if exists {
+                ExprKind::Exists(var, body)
+            } else {
+                ExprKind::Forall(var, body)
+            };
+            Ok(Expr { kind, span })
+        } else {
+            self.comparison()
+        }
+    }
+
+    // Parse a comparison between two terms
+    fn comparison(&mut self) -> ParseResult<Expr> {
+        let left = self.primary()?;
+
+        // `i.label in {"a", "b"}` or `i.label in class`
+        if let ExprKind::Label(name) = &left.kind {
+            if self.check(Token::In) {
+                self.current += 1;
+                let letters = match self.peek() {
+                    Some(Token::Identifier(_)) => {
+                        LetterSet::Class(self.identifier("letter class")?)
+                    }
+                    _ => LetterSet::Letters(self.letter_set()?),
+                };
+                return Ok(Expr {
+                    kind: ExprKind::In(name.clone(), letters),
+                    span: left.span.to(self.previous_span()),
+                });
+            }
+        }
+
+        // Parse a binary operator
+        let comparison = match self.peek() {
+            Some(Token::LessEqual) => Comparison::LessEqual,
+            Some(Token::Less) => Comparison::Less,
+            Some(Token::Equal) => Comparison::Equal,
+            Some(Token::NotEqual) => Comparison::NotEqual,
+            Some(Token::GreaterEqual) => Comparison::GreaterEqual,
+            Some(Token::Greater) => Comparison::Greater,
+            Some(Token::Define) => {
+                return Err(self
+                    .error_here("unexpected `=` in a condition")
+                    .with_help("equality is written `==`"))
+            }
+            _ => return Ok(left),
+        };
+        let operator = self.current_span();
+        self.current += 1;
+        let right = self.primary()?;
+        if self.is_comparison() {
+            return Err(self
+                .error_here("comparisons cannot be chained")
+                .with_help("split the comparison with `&&`, as in `i < k && k < j`"));
+        }
+        let span = left.span.to(right.span);
+        Ok(Expr {
+            kind: ExprKind::Compare(Box::new(left), comparison, operator, Box::new(right)),
+            span,
+        })
+    }
+
+    // Whether the next token is a comparison operator
+    fn is_comparison(&self) -> bool {
+        matches!(
+            self.peek(),
+            Some(
+                Token::LessEqual
+                    | Token::Less
+                    | Token::Equal
+                    | Token::NotEqual
+                    | Token::GreaterEqual
+                    | Token::Greater
+            )
+        )
+    }
+
+    // Parse a parenthesised boolean expression or a term
+    fn primary(&mut self) -> ParseResult<Expr> {
+        if self.check(Token::LeftParen) {
+            let start = self.current_span();
+            self.current += 1;
+            let expr = self.b_expression()?;
+            self.expect(Token::RightParen)?;
+            Ok(Expr {
+                kind: ExprKind::Paren(Box::new(expr)),
+                span: start.to(self.previous_span()),
+            })
+        } else {
+            self.term()
+        }
+    }
+
+    // Parse a term (string, number, variable, variable plus or minus a
+    // number, label, `first(i)` or `last(i)`)
+    fn term(&mut self) -> ParseResult<Expr> {
+        let start = self.current_span();
+        let kind = match self.peek().cloned() {
+            // strings are compared with labels
+            Some(Token::String(s)) => {
+                self.current += 1;
+                ExprKind::Str(s)
+            }
+            Some(Token::Identifier(name)) => {
+                self.current += 1;
+                let name = Name { name, span: start };
+                if self.check(Token::Plus) || self.check(Token::Minus) {
+                    let sign = if self.check(Token::Plus) { 1 } else { -1 };
+                    self.current += 1;
+                    match self.peek().cloned() {
+                        Some(Token::Number(offset)) => {
+                            self.current += 1;
+                            ExprKind::Offset(name, sign * offset)
+                        }
+                        _ => return Err(self.unexpected("number")),
+                    }
+                } else {
+                    ExprKind::Var(name)
+                }
+            }
+            Some(Token::Label(name)) => {
+                self.current += 1;
+                ExprKind::Label(Name { name, span: start })
+            }
+            Some(Token::Number(n)) => {
+                self.current += 1;
+                ExprKind::Number(n)
+            }
+            // `first(i)` is `i == 0` and `last(i)` is `i + 1 == n`
+            Some(Token::First) | Some(Token::Last) => {
+                let last = self.check(Token::Last);
+                self.current += 1;
+                self.expect(Token::LeftParen)?;
+                let var = self.identifier("position variable")?;
+                self.expect(Token::RightParen)?;
+                if last {
+                    ExprKind::Last(var)
+                } else {
+                    ExprKind::First(var)
+                }
+            }
+            Some(Token::True) => {
+                self.current += 1;
+                ExprKind::True
+            }
+            Some(Token::False) => {
+                self.current += 1;
+                ExprKind::False
+            }
+            _ => return Err(self.unexpected("expression")),
+        };
+        Ok(Expr {
+            kind,
+            span: start.to(self.previous_span()),
+        })
+    }
+
+    // Parse a set of letters `{"a", "b"}`
+    fn letter_set(&mut self) -> ParseResult<Vec<Letter>> {
+        self.expect(Token::LeftBrace)?;
+        let mut letters = Vec::new();
+        while !self.check(Token::RightBrace) {
+            if !letters.is_empty() {
+                self.expect(Token::Comma)?;
+            }
+            match self.peek().cloned() {
+                Some(Token::String(letter)) => {
+                    self.current += 1;
+                    letters.push(Letter {
+                        letter,
+                        quoted: true,
+                        span: self.previous_span(),
+                    });
+                }
+                _ => return Err(self.unexpected("letter")),
+            }
+        }
+        self.current += 1;
+        Ok(letters)
+    }
+
+    // Parse a letter substitution `{a -> b, "#" -> a}` from input letters
+    // to output letters, where the letters that are not mapped are kept
+    fn letter_map(&mut self) -> ParseResult<Vec<(Letter, Letter)>> {
+        self.expect(Token::LeftBrace)?;
+        let mut table = Vec::new();
+        while !self.check(Token::RightBrace) {
+            if !table.is_empty() {
+                self.expect(Token::Comma)?;
+            }
+            let from = self.letter()?;
+            self.expect(Token::Arrow)?;
+            let to = self.letter()?;
+            table.push((from, to));
+        }
+        self.current += 1;
+        Ok(table)
+    }
+
+    // Parse a letter, written as a string or as a bare identifier
+    fn letter(&mut self) -> ParseResult<Letter> {
+        let quoted = match self.peek() {
+            Some(Token::String(_)) => true,
+            Some(Token::Identifier(_)) => false,
+            _ => return Err(self.unexpected("letter")),
+        };
+        self.current += 1;
+        let letter = match &self.tokens[self.current - 1].token {
+            Token::String(s) => s.clone(),
+            _ => self.tokens[self.current - 1].text.clone(),
+        };
+        Ok(Letter {
+            letter,
+            quoted,
+            span: self.previous_span(),
+        })
+    }
+
+    // Parse an identifier, `expected` saying what it names in the error
+    fn identifier(&mut self, expected: &str) -> ParseResult<Name> {
+        match self.peek().cloned() {
+            Some(Token::Identifier(name)) => {
+                self.current += 1;
+                Ok(Name {
+                    name,
+                    span: self.previous_span(),
+                })
+            }
+            _ => Err(self.unexpected(expected)),
+        }
+    }
+
+    // Peek at the current token without consuming it
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.current).map(|t| &t.token)
+    }
+
+    // Whether the next tokens are `name(`, where `name` is not a keyword
+    fn check_call(&self, name: &str) -> bool {
+        matches!(self.peek(), Some(Token::Identifier(_)))
+            && self.tokens[self.current].text == name
+            && matches!(
+                self.tokens.get(self.current + 1).map(|t| &t.token),
+                Some(Token::LeftParen)
+            )
+    }
+
+    // Check if the current token matches the given token
+    fn check(&self, token: Token) -> bool {
+        matches!(self.peek(), Some(t) if *t == token)
+    }
+
+    // Consume the current token if it matches the given token, otherwise fail
+    fn expect(&mut self, token: Token) -> ParseResult<()> {
+        if self.check(token.clone()) {
+            self.current += 1;
+            Ok(())
+        } else {
+            Err(self.unexpected(&token.to_string()))
+        }
+    }
+
+    // The span of the current token, or of the end of the file
+    fn current_span(&self) -> Span {
+        match self.tokens.get(self.current) {
+            Some(t) => t.span,
+            None => self.end_of_file_span(),
+        }
+    }
+
+    // The span of the last consumed token
+    fn previous_span(&self) -> Span {
+        match self.current.checked_sub(1).and_then(|i| self.tokens.get(i)) {
+            Some(t) => t.span,
+            None => self.current_span(),
+        }
+    }
+
+    fn end_of_file_span(&self) -> Span {
+        match self.tokens.last() {
+            Some(t) => Span::new(t.span.end, t.span.end),
+            None => Span::default(),
+        }
+    }
+
+    // An error located at the current token
+    fn error_here(&self, message: impl Into<String>) -> Diagnostic {
+        Diagnostic::error(message, self.current_span())
+    }
+
+    // An error saying that `expected` was expected instead of the current token
+    fn unexpected(&self, expected: &str) -> Diagnostic {
+        match self.tokens.get(self.current) {
+            Some(t) => self.error_here(format!("expected {}, found `{}`", expected, t.text)),
+            None => self.error_here(format!("expected {}, found end of file", expected)),
+        }
+    }
+
+    // Skip tokens after an error until the beginning of the next
+    // top-level statement
+    fn synchronize(&mut self) {
+        let mut depth = self.depth;
+        self.depth = 0;
+        self.loops = 0;
+        let mut first = true;
+        while let Some(token) = self.peek() {
+            match token {
+                Token::Print
+                | Token::For
+                | Token::Repeat
+                | Token::Break
+                | Token::Proc
+                | Token::Transducer
+                | Token::Main
+                | Token::If
+                | Token::Var
+                | Token::Class
+                | Token::Map
+                | Token::Skip
+                | Token::Initialize
+                    if depth == 0 && !first =>
+                {
+                    return
+                }
+                Token::LeftBrace | Token::Do | Token::Then => depth += 1,
+                Token::RightBrace | Token::End => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+            self.current += 1;
+            first = false;
+        }
+    }
+
+    // Parse a bound of a for loop range: a constant, `n` or a position
+    // variable, possibly plus or minus a constant
+    fn range_bound(&mut self) -> ParseResult<Expr> {
+        match self.peek() {
+            Some(Token::Number(_)) | Some(Token::Identifier(_)) => self.term(),
+            _ => Err(self.unexpected("`0`, `n` or a position variable")),
+        }
+    }
+}
+
+//print ast
+pub fn print_ast(stmts: &Vec<Stmt>, indent: usize) {
+    for stmt in stmts {
+        print_stmt(stmt, indent);
+    }
+}
+
+fn print_stmt(stmt: &Stmt, indent: usize) {
+    let indent_str = " ".repeat(indent);
+
+    match stmt {
+        Stmt::Print(expr, _) => {
+            println!("{}Print:", indent_str);
+            print_pexpr(expr, indent + 2);
+        }
+        Stmt::For0(var, body) => {
+            println!("{}For0 {}:", indent_str, var);
+            print_ast(body, indent + 2);
+        }
+        Stmt::For1(var, body) => {
+            println!("{}For1 {}:", indent_str, var);
+            print_ast(body, indent + 2);
+        }
+        Stmt::If(condition, then_branch) => {
+            println!("{}If:", indent_str);
+            print_bexpr(condition, indent + 2);
+            println!("{}Then:", indent_str);
+            print_ast(then_branch, indent + 2);
+        }
+        Stmt::Declare(flag, value) => {
+            println!("{}Declare {}:", indent_str, flag);
+            print_bexpr(value, indent + 2);
+        }
+        Stmt::Assign(flag, value) => {
+            println!("{}Assign {}:", indent_str, flag);
+            print_bexpr(value, indent + 2);
+        }
+        Stmt::Break => println!("{}Break", indent_str),
+        Stmt::ForConst(var, positions, body) => {
+            let positions: Vec<String> = positions.iter().map(|p| p.to_string()).collect();
+            println!("{}ForConst {} [{}]:", indent_str, var, positions.join(", "));
+            print_ast(body, indent + 2);
+        }
+        Stmt::Repeat(count, body) => {
+            println!("{}Repeat {}:", indent_str, count);
+            print_ast(body, indent + 2);
+        }
+    }
+}
+
+fn print_pexpr(expr: &Pexpr, indent: usize) {
+    let indent_str = " ".repeat(indent);
+    match expr {
+        Pexpr::Label(label) => println!("{}Label: {}", indent_str, label),
+        Pexpr::Str(s) => println!("{}Str: {}", indent_str, s),
+        Pexpr::Map(table, label) => println!("{}Map: {:?} {}", indent_str, table, label),
+    }
+}
+
+fn print_bexpr(expr: &Bexpr, indent: usize) {
+    let indent_str = " ".repeat(indent);
+    match expr {
+        Bexpr::Var(var) => println!("{}Var: {}", indent_str, var),
+        Bexpr::True => println!("{}True", indent_str),
+        Bexpr::False => println!("{}False", indent_str),
+        Bexpr::Str(s) => println!("{}Str: {}", indent_str, s),
+        Bexpr::LessEqual(left, right) => {
+            println!("{}LessEqual:", indent_str);
+            print_bexpr(left, indent + 2);
+            print_bexpr(right, indent + 2);
+        }
+        Bexpr::Less(left, right) => {
+            println!("{}Less:", indent_str);
+            print_bexpr(left, indent + 2);
+            print_bexpr(right, indent + 2);
+        }
+        Bexpr::Equal(left, right) => {
+            println!("{}Equal:", indent_str);
+            print_bexpr(left, indent + 2);
+            print_bexpr(right, indent + 2);
+        }
+        Bexpr::NotEqual(left, right) => {
+            println!("{}NotEqual:", indent_str);
+            print_bexpr(left, indent + 2);
+            print_bexpr(right, indent + 2);
+        }
+        Bexpr::GreaterEqual(left, right) => {
+            println!("{}GreaterEqual:", indent_str);
+            print_bexpr(left, indent + 2);
+            print_bexpr(right, indent + 2);
+        }
+        Bexpr::Greater(left, right) => {
+            println!("{}Greater:", indent_str);
+            print_bexpr(left, indent + 2);
+            print_bexpr(right, indent + 2);
+        }
+        Bexpr::Not(expr) => {
+            println!("{}Not:", indent_str);
+            print_bexpr(expr, indent + 2);
+        }
+        Bexpr::Label(label) => println!("{}Label: {}", indent_str, label),
+        Bexpr::And(left, right) => {
+            println!("{}And:", indent_str);
+            print_bexpr(left, indent + 2);
+            print_bexpr(right, indent + 2);
+        }
+        Bexpr::Or(left, right) => {
+            println!("{}Or:", indent_str);
+            print_bexpr(left, indent + 2);
+            print_bexpr(right, indent + 2);
+        }
+        Bexpr::Offset(var, offset) => println!("{}Offset: {} {:+}", indent_str, var, offset),
+        Bexpr::Number(n) => println!("{}Number: {}", indent_str, n),
+        Bexpr::LetterIn(label, letters) => {
+            println!("{}LetterIn: {} {:?}", indent_str, label, letters)
+        }
+        Bexpr::Flag(flag) => println!("{}Flag: {}", indent_str, flag),
+        Bexpr::Exists(var, expr) => {
+            println!("{}Exists {}:", indent_str, var);
+            print_bexpr(expr, indent + 2);
+        }
+        Bexpr::Forall(var, expr) => {
+            println!("{}Forall {}:", indent_str, var);
+            print_bexpr(expr, indent + 2);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Alphabet;
+    use crate::diagnostic::Location;
+    use crate::lexer::{tokenize, tokenize_with};
+
+    fn parse(source: &str) -> Result<Vec<Stmt>, Vec<Diagnostic>> {
+        tokenize(source)
+            .and_then(|tokens| Parser::new(tokens).parse())
+            .map(|program| program.stmts)
+    }
+
+    #[test]
+    fn test_lexer_error_location() {
+        let errors = parse("for i in 0..n {\n    print(\"ab)\n}\n").unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "unterminated string literal");
+        assert_eq!(errors[0].span.start, Location::new(2, 11));
+    }
+
+    #[test]
+    fn test_parser_reports_every_statement() {
+        let source =
+            "for i in 0..n {\n  print(x)\n}\nfor j in 0..True {\n  print(j.label)\n}\nprint(\"a\")\n";
+        let errors = parse(source).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].message, "expected string or label, found `x`");
+        assert_eq!(errors[0].span.start, Location::new(2, 9));
+        assert_eq!(errors[1].span.start, Location::new(4, 13));
+
+        let rendered = errors[1].render(source, "test.txt");
+        assert!(rendered.contains(" --> test.txt:4:13\n"));
+        assert!(rendered.contains("4 | for j in 0..True {\n  |             ^^^^\n"));
+    }
+
+    #[test]
+    fn test_chained_comparisons() {
+        let errors =
+            parse("for i in 0..n {\n  for j in 0..n {\n    if i < j < n {\n      print(\"a\")\n    }\n  }\n}\n")
+                .unwrap_err();
+        assert_eq!(errors[0].message, "comparisons cannot be chained");
+        assert_eq!(errors[0].span.start, Location::new(3, 14));
+    }
+
+    #[test]
+    fn test_constant_loops() {
+        // the loops are kept for the compiler to unroll them, up to a bound
+        let stmts = parse("for i in 3..0 { print(i.label) }\nrepeat 2 { print(\"a\") }").unwrap();
+        assert!(matches!(
+            &stmts[..],
+            [Stmt::ForConst(_, positions, _), Stmt::Repeat(2, _)]
+                if matches!(positions[..], [Bexpr::Number(2), Bexpr::Number(1), Bexpr::Number(0)])
+        ));
+        let errors = parse(
+            "for i in n - 1000000000..n { print(i.label) }\nrepeat 2000000000 { print(\"a\") }",
+        )
+        .unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(
+            errors[0].message,
+            "the range goes 1000000000 positions away from the end of the word"
+        );
+        assert_eq!(errors[0].span.start, Location::new(1, 10));
+        assert_eq!(errors[1].message, "`repeat` runs its body 2000000000 times");
+        assert_eq!(errors[1].span.start, Location::new(2, 8));
+    }
+
+    #[test]
+    fn test_boolean_variables() {
+        let stmts =
+            parse("var b := False\nfor i in 0..n {\n  if b == False {\n    b := True\n  }\n}\n")
+                .unwrap();
+        match &stmts[1] {
+            Stmt::For0(_, body) => match &body[0] {
+                Stmt::If(Bexpr::Not(flag), _) => assert!(matches!(**flag, Bexpr::Flag(_))),
+                other => panic!("unexpected statement {:?}", other),
+            },
+            other => panic!("unexpected statement {:?}", other),
+        }
+
+        // flags are scoped by blocks
+        let errors = parse("for i in 0..n {\n  var b := True\n}\nb := False\n").unwrap_err();
+        assert_eq!(errors[0].message, "cannot assign to `b`");
+        assert_eq!(errors[0].span.start, Location::new(4, 1));
+    }
+
+    #[test]
+    fn test_alphabet() {
+        let program = tokenize("alphabet { input \"xy\" }\nprint(\"y\")\n")
+            .and_then(|tokens| Parser::new(tokens).parse())
+            .unwrap();
+        assert_eq!(program.alphabet.output, vec!["x", "y"]);
+
+        let source = "alphabet {\n  input \"xy\"\n  output \"x\"\n}\nfor i in 0..n {\n  if i.label == \"z\" {\n    print(\"y\")\n  }\n}\nfor j in 0..n {\n  print(j.label)\n}\n";
+        let errors = parse(source).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(
+            errors[0].message,
+            "\"z\" is not a letter of the input alphabet"
+        );
+        assert_eq!(errors[0].span.start, Location::new(6, 17));
+        assert_eq!(
+            errors[1].message,
+            "the input letter `y` is not in the output alphabet"
+        );
+        assert_eq!(errors[1].span.start, Location::new(11, 9));
+
+        let errors = parse("alphabet { input \"aa\" }").unwrap_err();
+        assert_eq!(errors[0].message, "letter `a` is declared twice");
+    }
+
+    #[test]
+    fn test_letter_classes() {
+        let stmts = parse("class ab = {\"a\", \"b\"}\nfor i in 0..n {\n  if i.label in ab {\n    print(\"a\")\n  }\n}\n").unwrap();
+        match &stmts[0] {
+            Stmt::For0(_, body) => match &body[0] {
+                Stmt::If(Bexpr::LetterIn(_, letters), _) => assert_eq!(letters, &["a", "b"]),
+                other => panic!("unexpected statement {:?}", other),
+            },
+            other => panic!("unexpected statement {:?}", other),
+        }
+
+        let errors = parse("for i in 0..n {\n  if i.label in vowels {\n    print(\"a\")\n  }\n}\n")
+            .unwrap_err();
+        assert_eq!(errors[0].message, "unknown letter class `vowels`");
+        assert_eq!(errors[0].span.start, Location::new(2, 17));
+    }
+
+    #[test]
+    fn test_procedures() {
+        let stmts =
+            parse("proc twice(i) { print(i.label) print(i.label) } for j in 0..n { twice(j) }")
+                .unwrap();
+        match &stmts[..] {
+            [Stmt::For0(j, body)] => match &body[..] {
+                [Stmt::Print(Pexpr::Label(first), _), Stmt::Print(Pexpr::Label(second), _)] => {
+                    assert_eq!(first, j);
+                    assert_eq!(second, j);
+                }
+                _ => panic!("the body is not inlined: {:?}", body),
+            },
+            _ => panic!("unexpected statements {:?}", stmts),
+        }
+
+        let errors = parse("for i in 0..n { twice(i) }").unwrap_err();
+        assert_eq!(errors[0].message, "unknown procedure `twice`");
+        let errors =
+            parse("proc twice(i) { print(i.label) }\nfor i in 0..n { twice(i, i) }").unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "`twice` takes 1 position but 2 were given"
+        );
+        assert_eq!(errors[0].span.start, Location::new(2, 17));
+        let errors = parse("for i in 0..n { proc f(j) { print(j.label) } }").unwrap_err();
+        assert_eq!(errors[0].message, "procedures are defined at the top level");
+    }
+
+    #[test]
+    fn test_quantifiers() {
+        let stmts = parse(
+            "for i in 0..n { if exists k.k < i && k.label == \"a\" || i == 0 { print(\"a\") } }",
+        )
+        .unwrap();
+        match &stmts[..] {
+            [Stmt::For0(i, body)] => match &body[..] {
+                // the body of the quantifier extends to the right
+                [Stmt::If(Bexpr::Exists(k, inner), _)] => assert_eq!(
+                    inner.to_string(),
+                    format!("( ( ({k} < {i}) && ({k} == \"a\") ) || ({i} == 0) )")
+                ),
+                _ => panic!("unexpected body {:?}", body),
+            },
+            _ => panic!("unexpected statements {:?}", stmts),
+        }
+
+        let errors = parse("for i in 0..n { if forall k k < i { print(\"a\") } }").unwrap_err();
+        assert!(errors[0].message.starts_with("expected `.`"));
+    }
+
+    #[test]
+    fn test_break() {
+        let errors = parse("for i in 0..n { print(i.label) }\nbreak").unwrap_err();
+        assert_eq!(errors[0].message, "`break` outside of a loop");
+        assert_eq!(errors[0].span.start, Location::new(2, 1));
+        let errors = parse("proc stop() { break } for i in 0..n { stop() }").unwrap_err();
+        assert_eq!(errors[0].message, "`break` outside of a loop");
+    }
+
+    #[test]
+    fn test_paper_syntax() {
+        let parse_paper = |source: &str| {
+            tokenize_with(source, Syntax::Paper)
+                .and_then(|tokens| Parser::new(tokens).parse_paper())
+                .map(|program| program.stmts)
+        };
+        let stmts =
+            parse_paper("for i in stdin <- do if first(i) then skip else print(i.label) end end")
+                .unwrap();
+        match &stmts[..] {
+            [Stmt::For1(_, body)] => assert_eq!(body.len(), 2),
+            _ => panic!("unexpected statements {:?}", stmts),
+        }
+
+        // one diagnostic per erroneous command
+        let errors =
+            parse_paper("for i in stdin -> do b = True end\nprint(\"a\") end").unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].message, "cannot assign to `b`");
+        assert_eq!(errors[1].span.start, Location::new(2, 12));
+        let errors = parse_paper("if True then print(\"a\")").unwrap_err();
+        assert_eq!(errors[0].message, "expected `end`, found end of file");
+    }
+
+    #[test]
+    fn test_transducers() {
+        let pipeline =
+            |source: &str| tokenize(source).and_then(|tokens| Parser::new(tokens).parse_pipeline());
+        let source = r#"
+            transducer copy { for i in 0..n { print(i.label) } }
+            transducer upper {
+                alphabet { input "ab#" output "AB" }
+                for i in 0..n { if i.label == "a" { print("A") } else { print("B") } }
+            }
+            main = upper . copy.copy
+        "#;
+        let stages = pipeline(source).unwrap();
+        assert_eq!(stages.len(), 3);
+        assert_eq!(stages[2].alphabet.output, vec!["A", "B"]);
+        assert!(parse(source).is_err());
+
+        let errors = pipeline(&source.replace("upper . copy.copy", "copy . upper")).unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "`upper` prints \"A\", which `copy` cannot read"
+        );
+        let errors = pipeline(&source.replace("upper . copy", "upper . cpy")).unwrap_err();
+        assert_eq!(errors[0].message, "unknown transducer `cpy`");
+        let errors = pipeline(&source.replace("main = upper . copy.copy", "")).unwrap_err();
+        assert_eq!(errors[0].message, "missing `main`");
+        let errors = pipeline(&format!("{}\nprint(\"a\")", source)).unwrap_err();
+        assert_eq!(errors[0].message, "statement outside of a transducer");
+    }
+
+    #[test]
+    fn test_letter_maps() {
+        let stmts =
+            parse("map swap = {a -> b, b -> a}\nfor i in 0..n {\n  print(swap[i.label])\n}\n")
+                .unwrap();
+        match &stmts[0] {
+            Stmt::For0(_, body) => match &body[0] {
+                Stmt::Print(Pexpr::Map(table, _), _) => assert_eq!(table.len(), 2),
+                other => panic!("unexpected statement {:?}", other),
+            },
+            other => panic!("unexpected statement {:?}", other),
+        }
+
+        let source =
+            "alphabet {\n  input \"abc\"\n  output \"ab\"\n}\nmap swap = {a -> b, b -> a}\n";
+        let errors = parse(source).unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "the input letter `c` is not mapped by `swap` and is not in the output alphabet"
+        );
+        assert_eq!(errors[0].span.start, Location::new(5, 5));
+
+        let errors = parse("map m = {a -> b, a -> a}").unwrap_err();
+        assert_eq!(errors[0].message, "`a` is mapped twice");
+    }
+
+    #[test]
+    fn test_symbols() {
+        let source = r#"
+            alphabet {
+                input {"open", "close", "x"}
+                output {"open", "close", "x", "xx"}
+            }
+            for i in n..0 {
+                if i.label == "open" {
+                    print("close")
+                } else if i.label == "close" {
+                    print("open")
+                } else {
+                    print("xx" + "x")
+                }
+            }
+        "#;
+        let program = Parser::new(tokenize(source).unwrap()).parse().unwrap();
+        let word = program.alphabet.split_word("open x,close").unwrap();
+        assert_eq!(word, ["open", "x", "close"]);
+        let mut interpreter = crate::Interpreter::with_letters(word);
+        assert_eq!(
+            interpreter.run_letters(&program.stmts),
+            ["open", "xx", "x", "close"]
+        );
+        assert_eq!(program.alphabet.split_word("open y"), Err("y".to_string()));
+
+        // the letters of a string are its characters, unless it is a letter
+        let default = Alphabet::default();
+        assert_eq!(default.split_word("ab #").unwrap(), ["a", "b", "#"]);
+        assert_eq!(default.split_word("abc"), Err("c".to_string()));
+        let errors = parse("alphabet { input {\"a b\"} }").unwrap_err();
+        assert_eq!(errors[0].message, "`a b` is not a letter");
+        let errors = parse(&source.replace("\"xx\" + \"x\"", "\"xo\"")).unwrap_err();
+        assert_eq!(errors[0].message, "`o` is not in the output alphabet");
+    }
+
+    #[test]
+    fn test_input_modes() {
+        let program =
+            |source: &str| tokenize(source).and_then(|tokens| Parser::new(tokens).parse());
+        let reverse = "for i in n..0 { print(i.label) }";
+        let run = |source: &str, word: &str| {
+            let parsed = program(source).unwrap();
+            let letters = parsed.alphabet.split_word(word).unwrap();
+            let output = crate::Interpreter::with_letters(letters).run_letters(&parsed.stmts);
+            parsed.alphabet.join_word(&output)
+        };
+
+        // the letters are the Unicode scalar values, an accent included
+        let scalars = format!("alphabet {{ input \"aeé\u{301}\" }} {}", reverse);
+        assert_eq!(run(&scalars, "aé"), "éa");
+        assert_eq!(run(&scalars, "ae\u{301}"), "\u{301}ea");
+
+        // or the grapheme clusters
+        let graphemes = format!(
+            "alphabet {{ mode graphemes input \"ae\u{301}\" }} {}",
+            reverse
+        );
+        let parsed = program(&graphemes).unwrap();
+        assert_eq!(parsed.alphabet.input, ["a", "e\u{301}"]);
+        assert_eq!(run(&graphemes, "e\u{301}a"), "ae\u{301}");
+        assert_eq!(parsed.alphabet.split_word("e"), Err("e".to_string()));
+
+        // or the bytes, written as escapes when they are not ASCII
+        let bytes = r#"
+            alphabet { mode bytes input "aé" }
+            for i in 0..n {
+                if i.label == "\xc3" {
+                    print("é")
+                } else if i.label == "a" {
+                    print(i.label + i.label)
+                }
+            }
+        "#;
+        let parsed = program(bytes).unwrap();
+        assert_eq!(parsed.alphabet.input, ["a", "\\xc3", "\\xa9"]);
+        assert_eq!(
+            parsed.alphabet.split_word("éa").unwrap(),
+            ["\\xc3", "\\xa9", "a"]
+        );
+        assert_eq!(run(bytes, "aéa"), "aaéaa");
+
+        let errors = program("alphabet { mode words input \"ab\" }").unwrap_err();
+        assert_eq!(
+            errors[0].help.as_deref(),
+            Some("the modes are bytes, scalars, graphemes")
+        );
+        let errors = program("alphabet { input \"ab\" mode bytes }").unwrap_err();
+        assert_eq!(errors[0].message, "the mode is declared after the letters");
+    }
+}
@@ -0,0 +1,209 @@
+///
+/// The statements of a file as they are written, before they are checked
+/// and lowered into the statements of `ast`. The names are those of the
+/// source and every node knows its place in it, so that the checker can
+/// report its errors where they are, the formatter can print the file
+/// back and the language server can find what is under the cursor.
+///
+use crate::ast::{Alphabet, InputMode};
+use crate::diagnostic::Span;
+use crate::lexer::Syntax;
+use std::fmt;
+
+/// A parsed file, with its alphabet header
+#[derive(Debug, Clone)]
+pub struct Source {
+    pub syntax: Syntax,
+    pub header: Option<Header>,
+    pub statements: Vec<Statement>,
+}
+
+/// The `alphabet { ... }` header, with the sections that are written
+#[derive(Debug, Clone)]
+pub struct Header {
+    pub mode: Option<InputMode>,
+    pub input: Option<Section>,
+    pub output: Option<Section>,
+    pub span: Span,
+}
+
+/// The letters of a section of the header, the symbols of a string or a
+/// list of strings
+#[derive(Debug, Clone, PartialEq)]
+pub enum Section {
+    Symbols(String),
+    List(Vec<String>),
+}
+
+/// A statement of the brace syntax or a command of the paper syntax
+#[derive(Debug, Clone)]
+pub struct Statement {
+    pub kind: StatementKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum StatementKind {
+    Print(Vec<Printed>),               // print(e1 + e2 + ...)
+    For(Name, Range, Vec<Statement>),  // for i in ... { ... }
+    Repeat(i32, Span, Vec<Statement>), // repeat k { ... }, with the place of `k`
+    // if c1 { ... } else if c2 { ... } else { ... }
+    If(Vec<(Expr, Vec<Statement>)>, Option<Vec<Statement>>),
+    Declare(Name, Expr), // var b := c
+    Initialize(Name),    // initialize b
+    Assign(Name, Expr),  // b := c, or b = c in the paper syntax
+    Break,
+    Skip,
+    Proc(Name, Vec<Name>, Vec<Statement>), // proc f(i, j) { ... }
+    Call(Name, Vec<Name>),                 // f(i, j)
+    Class(Name, Vec<Letter>),              // class vowels = {"a", "e"}
+    Map(Name, Vec<(Letter, Letter)>),      // map swap = {a -> b, b -> a}
+    Transducer(Name, Option<Header>, Vec<Statement>), // transducer f { ... }
+    Main(Vec<Name>),                       // main = f . g
+}
+
+/// The positions visited by a loop
+#[derive(Debug, Clone)]
+pub enum Range {
+    // `start..end` or `start..=end`, going down when `start` is the upper bound
+    Positions {
+        start: Box<Expr>,
+        end: Box<Expr>,
+        inclusive: bool,
+    },
+    // `blocks(stdin, "#")`, or `rev(blocks(stdin, "#"))`
+    Blocks {
+        separator: Letter,
+        reversed: bool,
+    },
+    // the positions of a block `b`, or `rev(b)`
+    Block {
+        block: Name,
+        reversed: bool,
+    },
+    // `stdin ->`, or `stdin <-`, in the paper syntax
+    Stdin {
+        reversed: bool,
+    },
+}
+
+/// A term of a print statement
+#[derive(Debug, Clone)]
+pub enum Printed {
+    Str(Letter),     // "ab"
+    Label(Name),     // i.label
+    Map(Name, Name), // swap[i.label]
+}
+
+/// A letter written as a string, or in a letter map as an identifier
+#[derive(Debug, Clone, PartialEq)]
+pub struct Letter {
+    pub letter: String,
+    pub quoted: bool,
+    pub span: Span,
+}
+
+/// An identifier and its place. The place of the variable of a label
+/// `i.label` is the whole label.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Name {
+    pub name: String,
+    pub span: Span,
+}
+
+/// A condition, or a term of a condition
+#[derive(Debug, Clone)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum ExprKind {
+    True,
+    False,
+    Number(i32),
+    Str(String),
+    Var(Name),           // a position, `n` or a boolean variable
+    Offset(Name, i32),   // i + 1, n - 2
+    Label(Name),         // i.label
+    First(Name),         // first(i)
+    Last(Name),          // last(i)
+    In(Name, LetterSet), // i.label in {"a", "b"}, i.label in vowels
+    // the comparison with the place of its operator
+    Compare(Box<Expr>, Comparison, Span, Box<Expr>),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Exists(Name, Box<Expr>),
+    Forall(Name, Box<Expr>),
+    Paren(Box<Expr>),
+}
+
+/// The letters of `i.label in ...`
+#[derive(Debug, Clone)]
+pub enum LetterSet {
+    Class(Name),
+    Letters(Vec<Letter>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    LessEqual,
+    Less,
+    Equal,
+    NotEqual,
+    GreaterEqual,
+    Greater,
+}
+
+impl Comparison {
+    pub fn is_equality(self) -> bool {
+        matches!(self, Comparison::Equal | Comparison::NotEqual)
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            Comparison::LessEqual => "<=",
+            Comparison::Less => "<",
+            Comparison::Equal => "==",
+            Comparison::NotEqual => "!=",
+            Comparison::GreaterEqual => ">=",
+            Comparison::Greater => ">",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+impl Section {
+    /// The letters of the section, split into the symbols of the mode
+    pub fn letters(&self, mode: InputMode) -> Vec<String> {
+        match self {
+            Section::Symbols(s) => mode.split(s),
+            Section::List(letters) => letters.clone(),
+        }
+    }
+}
+
+impl Header {
+    /// The alphabets of the header, a missing section being the same as
+    /// the other one (the parser rejects headers without letters)
+    pub fn alphabet(&self) -> Alphabet {
+        let mode = self.mode.unwrap_or_default();
+        let input = self.input.as_ref().map(|section| section.letters(mode));
+        let output = self.output.as_ref().map(|section| section.letters(mode));
+        let (input, output) = match (input, output) {
+            (Some(input), Some(output)) => (input, output),
+            (Some(input), None) => (input.clone(), input),
+            (None, Some(output)) => (output.clone(), output),
+            (None, None) => (vec![], vec![]),
+        };
+        Alphabet {
+            input,
+            output,
+            mode,
+        }
+    }
+}